@@ -1153,6 +1153,73 @@ async fn api_v3_configure_table_create_no_fields() {
     );
 }
 
+#[test_log::test(tokio::test)]
+async fn api_v3_configure_table_create_explicit_series_key() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+    let table_url = format!("{base}/api/v3/configure/table", base = server.client_addr());
+
+    // tables have the tags they are created with as their series key, unless they declare it:
+    for (table, explicit_series_key) in [("bar", false), ("baz", true)] {
+        let resp = client
+            .post(&table_url)
+            .json(&json!({
+                "db": "foo",
+                "table": table,
+                "tags": ["one"],
+                "fields": [],
+                "explicit_series_key": explicit_series_key
+            }))
+            .send()
+            .await
+            .expect("create table call failed");
+        assert_eq!(StatusCode::OK, resp.status());
+    }
+
+    // a tag outside of the tags the table was created with is added to its series key:
+    server
+        .write_lp_to_db(
+            "foo",
+            "bar,one=1,two=2 val=0 2998574938",
+            influxdb3_client::Precision::Second,
+        )
+        .await
+        .expect("write to db");
+    let result = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            ("q", "SELECT * FROM bar"),
+            ("format", "json"),
+        ])
+        .await
+        .json::<Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        json!([{
+            "one": "1",
+            "two": "2",
+            "val": 0.0,
+            "time": "2065-01-07T17:28:58"
+        }])
+    );
+
+    // unless the series key was declared:
+    let error = server
+        .write_lp_to_db(
+            "foo",
+            "baz,one=1,two=2 val=0 2998574938",
+            influxdb3_client::Precision::Second,
+        )
+        .await
+        .expect_err("write with a tag outside the series key should fail");
+    assert_contains!(
+        error.to_string(),
+        "tag 'two' on line 1 is not in the series key of table 'baz'"
+    );
+}
+
 #[test_log::test(tokio::test)]
 async fn api_v3_configure_table_delete() {
     let db_name = "foo";
@@ -1273,6 +1273,7 @@ mod tests {
                 map
             },
             processing_engine_triggers: Default::default(),
+            default_tags: Default::default(),
             deleted: false,
         };
         let table_id = TableId::from(0);
@@ -10,10 +10,10 @@ use indexmap::IndexMap;
 use influxdb_line_protocol::FieldValue;
 use influxdb3_id::{ColumnId, DbId, SerdeVecMap, TableId};
 use influxdb3_wal::{
-    CatalogBatch, CatalogOp, DeleteDatabaseDefinition, DeleteTableDefinition,
-    DeleteTriggerDefinition, DistinctCacheDefinition, DistinctCacheDelete, FieldAdditions,
-    FieldDefinition, LastCacheDefinition, LastCacheDelete, OrderedCatalogBatch, TriggerDefinition,
-    TriggerIdentifier,
+    CatalogBatch, CatalogOp, DefaultTagsDefinition, DeleteDatabaseDefinition,
+    DeleteTableDefinition, DeleteTriggerDefinition, DistinctCacheDefinition, DistinctCacheDelete,
+    FieldAdditions, FieldDefinition, LastCacheDefinition, LastCacheDelete, OrderedCatalogBatch,
+    TriggerDefinition, TriggerIdentifier,
 };
 use iox_time::Time;
 use observability_deps::tracing::{debug, info, warn};
@@ -463,6 +463,8 @@ pub struct DatabaseSchema {
     pub tables: SerdeVecMap<TableId, Arc<TableDefinition>>,
     pub table_map: BiHashMap<TableId, Arc<str>>,
     pub processing_engine_triggers: HashMap<String, TriggerDefinition>,
+    /// Tags that are added to every line written to the database that does not set them
+    pub default_tags: SerdeVecMap<Arc<str>, Arc<str>>,
     pub deleted: bool,
 }
 
@@ -474,6 +476,7 @@ impl DatabaseSchema {
             tables: Default::default(),
             table_map: BiHashMap::new(),
             processing_engine_triggers: HashMap::new(),
+            default_tags: Default::default(),
             deleted: false,
         }
     }
@@ -660,6 +663,7 @@ impl UpdateDatabaseSchema for CatalogOp {
             CatalogOp::DisableTrigger(trigger_identifier) => {
                 DisableTrigger(trigger_identifier.clone()).update_schema(schema)
             }
+            CatalogOp::SetDefaultTags(default_tags) => default_tags.update_schema(schema),
         }
     }
 }
@@ -726,6 +730,24 @@ impl UpdateDatabaseSchema for DeleteTableDefinition {
     }
 }
 
+impl UpdateDatabaseSchema for DefaultTagsDefinition {
+    fn update_schema<'a>(
+        &self,
+        mut schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        let default_tags = self
+            .tags
+            .iter()
+            .map(|(key, value)| (Arc::clone(key), Arc::clone(value)))
+            .collect::<SerdeVecMap<_, _>>();
+        if schema.default_tags == default_tags {
+            return Ok(schema);
+        }
+        schema.to_mut().default_tags = default_tags;
+        Ok(schema)
+    }
+}
+
 struct EnableTrigger(TriggerIdentifier);
 struct DisableTrigger(TriggerIdentifier);
 
@@ -846,6 +868,9 @@ pub struct TableDefinition {
     pub last_caches: HashMap<Arc<str>, LastCacheDefinition>,
     pub distinct_caches: HashMap<Arc<str>, DistinctCacheDefinition>,
     pub deleted: bool,
+    /// Whether the series key was declared explicitly when the table was created, as opposed
+    /// to being made up of the tags written to the table
+    pub explicit_series_key: bool,
 }
 
 impl TableDefinition {
@@ -906,6 +931,7 @@ impl TableDefinition {
             last_caches: HashMap::new(),
             distinct_caches: HashMap::new(),
             deleted: false,
+            explicit_series_key: false,
         })
     }
 
@@ -919,13 +945,17 @@ impl TableDefinition {
                 field_def.data_type.into(),
             ));
         }
-        Self::new(
+        let table_def = Self::new(
             table_definition.table_id,
             Arc::clone(&table_definition.table_name),
             columns,
             table_definition.key.clone(),
         )
-        .expect("tables defined from ops should not exceed column limits")
+        .expect("tables defined from ops should not exceed column limits");
+        Self {
+            explicit_series_key: table_definition.explicit_series_key,
+            ..table_def
+        }
     }
 
     pub(crate) fn check_and_add_new_fields(
@@ -1312,6 +1342,7 @@ mod tests {
                 map
             },
             processing_engine_triggers: Default::default(),
+            default_tags: Default::default(),
             deleted: false,
         };
         use InfluxColumnType::*;
@@ -1521,6 +1552,7 @@ mod tests {
             tables: SerdeVecMap::new(),
             table_map: BiHashMap::new(),
             processing_engine_triggers: Default::default(),
+            default_tags: Default::default(),
             deleted: false,
         };
         database.tables.insert(
@@ -1579,6 +1611,7 @@ mod tests {
                 map
             },
             processing_engine_triggers: Default::default(),
+            default_tags: Default::default(),
             deleted: false,
         };
         use InfluxColumnType::*;
@@ -1621,6 +1654,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn serialize_explicit_series_key() {
+        use InfluxColumnType::*;
+        let table_def = TableDefinition::new(
+            TableId::from(0),
+            "test".into(),
+            vec![
+                (ColumnId::from(0), "tag_1".into(), Tag),
+                (ColumnId::from(1), "time".into(), Timestamp),
+            ],
+            vec![ColumnId::from(0)],
+        )
+        .unwrap();
+        // tables whose series key is made up of their tags serialize as they did before:
+        let serialized = serde_json::to_string(&table_def).unwrap();
+        assert!(!serialized.contains("explicit_series_key"));
+
+        let table_def = TableDefinition {
+            explicit_series_key: true,
+            ..table_def
+        };
+        let serialized = serde_json::to_string(&table_def).unwrap();
+        let deserialized: TableDefinition = serde_json::from_str(&serialized).unwrap();
+        assert!(deserialized.explicit_series_key);
+        assert_eq!(deserialized, table_def);
+    }
+
     #[test]
     fn serialize_last_cache() {
         let node_id = Arc::from("sample-host-id");
@@ -1636,6 +1696,7 @@ mod tests {
                 map
             },
             processing_engine_triggers: Default::default(),
+            default_tags: Default::default(),
             deleted: false,
         };
         use InfluxColumnType::*;
@@ -1790,6 +1851,7 @@ mod tests {
             tables: SerdeVecMap::new(),
             table_map: BiHashMap::new(),
             processing_engine_triggers: Default::default(),
+            default_tags: Default::default(),
             deleted: false,
         };
         let deleted_table_id = TableId::new();
@@ -1871,6 +1933,7 @@ mod tests {
                 FieldDefinition::new(ColumnId::from(2), "field", FieldDataType::String),
             ],
             key: vec![ColumnId::from(0)],
+            explicit_series_key: false,
         };
         let create_op = CatalogBatch {
             database_id: db_id,
@@ -2170,4 +2233,60 @@ mod tests {
             .unwrap();
         assert_eq!(2_000, catalog.inner.read().table_count());
     }
+
+    #[test]
+    fn set_default_tags() {
+        let catalog = Catalog::new(Arc::from("host"), Arc::from("instance"));
+        let db_id = DbId::new();
+        let set_default_tags = |catalog: &Catalog, tags: &[(&str, &str)]| {
+            catalog
+                .apply_catalog_batch(&create::catalog_batch(
+                    db_id,
+                    "foo",
+                    0,
+                    [create::set_default_tags_op(
+                        db_id,
+                        "foo",
+                        tags.iter()
+                            .map(|(key, value)| (Arc::from(*key), Arc::from(*value))),
+                    )],
+                ))
+                .unwrap()
+        };
+        let default_tags = |catalog: &Catalog| {
+            catalog
+                .db_schema("foo")
+                .unwrap()
+                .default_tags
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let batch = set_default_tags(&catalog, &[("environment", "prod")]).unwrap();
+        assert_eq!(
+            default_tags(&catalog),
+            [("environment".to_string(), "prod".to_string())]
+        );
+        // setting the same tags again does not change the catalog:
+        assert!(set_default_tags(&catalog, &[("environment", "prod")]).is_none());
+
+        // the default tags are kept in the catalog snapshot:
+        let serialized = serde_json::to_string(&catalog).unwrap();
+        let deserialized: Catalog = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(default_tags(&deserialized), default_tags(&catalog));
+
+        // and are set when the op is replayed from the WAL:
+        let replayed = Catalog::new(Arc::from("host"), Arc::from("instance"));
+        replayed.apply_ordered_catalog_batch(&batch).unwrap();
+        assert_eq!(default_tags(&replayed), default_tags(&catalog));
+
+        // the tags replace the default tags the database had:
+        set_default_tags(&catalog, &[("region", "us-east")]).unwrap();
+        assert_eq!(
+            default_tags(&catalog),
+            [("region".to_string(), "us-east".to_string())]
+        );
+        set_default_tags(&catalog, &[]).unwrap();
+        assert!(default_tags(&catalog).is_empty());
+    }
 }
@@ -111,6 +111,8 @@ struct DatabaseSnapshot {
     tables: SerdeVecMap<TableId, TableSnapshot>,
     #[serde(default)]
     processing_engine_triggers: SerdeVecMap<String, ProcessingEngineTriggerSnapshot>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    default_tags: Vec<(Arc<str>, Arc<str>)>,
     deleted: bool,
 }
 
@@ -129,6 +131,11 @@ impl From<&DatabaseSchema> for DatabaseSnapshot {
                 .iter()
                 .map(|(name, trigger)| (name.clone(), trigger.into()))
                 .collect(),
+            default_tags: db
+                .default_tags
+                .iter()
+                .map(|(key, value)| (Arc::clone(key), Arc::clone(value)))
+                .collect(),
             deleted: db.deleted,
         }
     }
@@ -170,6 +177,7 @@ impl From<DatabaseSnapshot> for DatabaseSchema {
             tables,
             table_map,
             processing_engine_triggers,
+            default_tags: snap.default_tags.into_iter().collect(),
             deleted: snap.deleted,
         }
     }
@@ -211,6 +219,8 @@ struct TableSnapshot {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     distinct_caches: Vec<DistinctCacheSnapshot>,
     deleted: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    explicit_series_key: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -375,6 +385,7 @@ impl From<&TableDefinition> for TableSnapshot {
             last_caches: def.last_caches.values().map(Into::into).collect(),
             distinct_caches: def.distinct_caches.values().map(Into::into).collect(),
             deleted: def.deleted,
+            explicit_series_key: def.explicit_series_key,
         }
     }
 }
@@ -464,6 +475,7 @@ impl From<TableSnapshot> for TableDefinition {
                 .into_iter()
                 .map(|dc_snap| (Arc::clone(&dc_snap.name), dc_snap.into()))
                 .collect(),
+            explicit_series_key: snap.explicit_series_key,
             ..table_def
         }
     }
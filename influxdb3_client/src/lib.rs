@@ -402,6 +402,7 @@ impl Client {
                             r#type: r#type.into(),
                         })
                         .collect(),
+                    explicit_series_key: false,
                 }),
                 None::<()>,
                 None,
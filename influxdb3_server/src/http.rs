@@ -1301,6 +1301,7 @@ where
             table,
             tags,
             fields,
+            explicit_series_key,
         } = self.read_body_json(req).await?;
         self.write_buffer
             .create_table(
@@ -1311,6 +1312,7 @@ where
                     .into_iter()
                     .map(|field| (field.name, field.r#type))
                     .collect(),
+                explicit_series_key,
             )
             .await?;
         Ok(Response::builder()
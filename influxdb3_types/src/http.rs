@@ -253,6 +253,10 @@ pub struct CreateTableRequest {
     pub table: String,
     pub tags: Vec<String>,
     pub fields: Vec<CreateTableField>,
+    /// Whether the tags are declared as the series key of the table, so lines written to it
+    /// with any other tag are rejected, rather than the tag being added to its series key
+    #[serde(default)]
+    pub explicit_series_key: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -5,6 +5,9 @@ use serde::{Deserialize, Serialize};
 #[serde(rename_all = "lowercase")]
 pub enum Precision {
     Auto,
+    /// Like `Auto`, but the precision is guessed once for the whole batch of lines, by a
+    /// majority vote on their timestamps, and applied uniformly to every line in the batch
+    AutoBatch,
     Second,
     Millisecond,
     Microsecond,
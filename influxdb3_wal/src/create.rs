@@ -101,6 +101,19 @@ pub fn create_table_op(
         table_id,
         field_definitions: fields.into_iter().collect(),
         key: key.into_iter().collect(),
+        explicit_series_key: false,
+    })
+}
+
+pub fn set_default_tags_op(
+    db_id: DbId,
+    db_name: impl Into<Arc<str>>,
+    tags: impl IntoIterator<Item = (Arc<str>, Arc<str>)>,
+) -> CatalogOp {
+    CatalogOp::SetDefaultTags(DefaultTagsDefinition {
+        database_id: db_id,
+        database_name: db_name.into(),
+        tags: tags.into_iter().collect(),
     })
 }
 
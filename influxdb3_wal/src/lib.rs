@@ -347,6 +347,7 @@ pub enum CatalogOp {
     DeleteTrigger(DeleteTriggerDefinition),
     EnableTrigger(TriggerIdentifier),
     DisableTrigger(TriggerIdentifier),
+    SetDefaultTags(DefaultTagsDefinition),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -379,6 +380,18 @@ pub struct WalTableDefinition {
     pub table_id: TableId,
     pub field_definitions: Vec<FieldDefinition>,
     pub key: Vec<ColumnId>,
+    /// Whether the key was declared explicitly, rather than made up of the tags written to the
+    /// table
+    pub explicit_series_key: bool,
+}
+
+/// Sets the tags that are added to every line written to a database that does not set them,
+/// replacing any default tags the database had
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DefaultTagsDefinition {
+    pub database_id: DbId,
+    pub database_name: Arc<str>,
+    pub tags: Vec<(Arc<str>, Arc<str>)>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
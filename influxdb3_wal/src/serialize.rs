@@ -5,6 +5,7 @@
 use crate::WalContents;
 use byteorder::{BigEndian, ReadBytesExt};
 use bytes::Bytes;
+use serde::Serialize;
 use std::io::Cursor;
 use std::mem::size_of;
 use thiserror::Error;
@@ -30,7 +31,11 @@ pub enum Error {
 pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
 
 /// The first bytes written into a wal file to identify it and its version.
-const FILE_TYPE_IDENTIFIER: &[u8] = b"idb3.001";
+const FILE_TYPE_IDENTIFIER: &[u8] = b"idb3.002";
+
+/// The identifier of wal files written by previous versions, whose contents are decoded as
+/// [`v1::WalContents`] and converted to the current [`WalContents`].
+const FILE_TYPE_IDENTIFIER_V1: &[u8] = b"idb3.001";
 
 #[inline(always)]
 pub fn verify_file_type_and_deserialize(b: Bytes) -> Result<WalContents> {
@@ -41,7 +46,7 @@ pub fn verify_file_type_and_deserialize(b: Bytes) -> Result<WalContents> {
     // Read and verify the file type identifier
     let file_type = &contents[..pos];
 
-    if file_type != FILE_TYPE_IDENTIFIER {
+    if file_type != FILE_TYPE_IDENTIFIER && file_type != FILE_TYPE_IDENTIFIER_V1 {
         return Err(Error::InvalidWalFile);
     }
 
@@ -62,14 +67,22 @@ pub fn verify_file_type_and_deserialize(b: Bytes) -> Result<WalContents> {
     }
 
     // Deserialize the data into a WalContents
-    let contents: WalContents = bitcode::deserialize(data)?;
+    let contents: WalContents = if file_type == FILE_TYPE_IDENTIFIER {
+        bitcode::deserialize(data)?
+    } else {
+        bitcode::deserialize::<v1::WalContents>(data)?.into()
+    };
 
     Ok(contents)
 }
 
 pub(crate) fn serialize_to_file_bytes(contents: &WalContents) -> Result<Vec<u8>> {
+    serialize_with_identifier(FILE_TYPE_IDENTIFIER, contents)
+}
+
+fn serialize_with_identifier<T: Serialize>(identifier: &[u8], contents: &T) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
-    buf.extend_from_slice(FILE_TYPE_IDENTIFIER);
+    buf.extend_from_slice(identifier);
 
     // serialize the contents into bitcode bytes
     let data = bitcode::serialize(contents)?;
@@ -86,11 +99,155 @@ pub(crate) fn serialize_to_file_bytes(contents: &WalContents) -> Result<Vec<u8>>
     Ok(buf)
 }
 
+/// The contents of wal files written with the [`FILE_TYPE_IDENTIFIER_V1`] identifier, before
+/// tables recorded whether their series key was declared, and databases could have their
+/// default tags set
+///
+/// Only the types whose layout changed, and the types that contain them, are defined here.
+mod v1 {
+    use crate::{
+        DatabaseDefinition, DeleteDatabaseDefinition, DeleteTableDefinition,
+        DeleteTriggerDefinition, DistinctCacheDefinition, DistinctCacheDelete, FieldAdditions,
+        FieldDefinition, LastCacheDefinition, LastCacheDelete, NoopDetails, SnapshotDetails,
+        TriggerDefinition, TriggerIdentifier, WalFileSequenceNumber, WriteBatch,
+    };
+    use influxdb3_id::{ColumnId, DbId, TableId};
+    use serde::{Deserialize, Serialize};
+    use std::sync::Arc;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub(super) struct WalContents {
+        pub(super) persist_timestamp_ms: i64,
+        pub(super) min_timestamp_ns: i64,
+        pub(super) max_timestamp_ns: i64,
+        pub(super) wal_file_number: WalFileSequenceNumber,
+        pub(super) ops: Vec<WalOp>,
+        pub(super) snapshot: Option<SnapshotDetails>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub(super) enum WalOp {
+        Write(WriteBatch),
+        Catalog(OrderedCatalogBatch),
+        Noop(NoopDetails),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub(super) struct OrderedCatalogBatch {
+        pub(super) catalog: CatalogBatch,
+        pub(super) database_sequence_number: u32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub(super) struct CatalogBatch {
+        pub(super) database_id: DbId,
+        pub(super) database_name: Arc<str>,
+        pub(super) time_ns: i64,
+        pub(super) ops: Vec<CatalogOp>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub(super) enum CatalogOp {
+        CreateDatabase(DatabaseDefinition),
+        CreateTable(WalTableDefinition),
+        AddFields(FieldAdditions),
+        CreateDistinctCache(DistinctCacheDefinition),
+        DeleteDistinctCache(DistinctCacheDelete),
+        CreateLastCache(LastCacheDefinition),
+        DeleteLastCache(LastCacheDelete),
+        DeleteDatabase(DeleteDatabaseDefinition),
+        DeleteTable(DeleteTableDefinition),
+        CreateTrigger(TriggerDefinition),
+        DeleteTrigger(DeleteTriggerDefinition),
+        EnableTrigger(TriggerIdentifier),
+        DisableTrigger(TriggerIdentifier),
+    }
+
+    /// A table definition from before tables recorded whether their series key was declared,
+    /// so all of them have a series key made up of the tags written to them
+    #[derive(Debug, Serialize, Deserialize)]
+    pub(super) struct WalTableDefinition {
+        pub(super) database_id: DbId,
+        pub(super) database_name: Arc<str>,
+        pub(super) table_name: Arc<str>,
+        pub(super) table_id: TableId,
+        pub(super) field_definitions: Vec<FieldDefinition>,
+        pub(super) key: Vec<ColumnId>,
+    }
+
+    impl From<WalContents> for crate::WalContents {
+        fn from(contents: WalContents) -> Self {
+            Self {
+                persist_timestamp_ms: contents.persist_timestamp_ms,
+                min_timestamp_ns: contents.min_timestamp_ns,
+                max_timestamp_ns: contents.max_timestamp_ns,
+                wal_file_number: contents.wal_file_number,
+                ops: contents.ops.into_iter().map(Into::into).collect(),
+                snapshot: contents.snapshot,
+            }
+        }
+    }
+
+    impl From<WalOp> for crate::WalOp {
+        fn from(op: WalOp) -> Self {
+            match op {
+                WalOp::Write(batch) => Self::Write(batch),
+                WalOp::Catalog(batch) => Self::Catalog(crate::OrderedCatalogBatch::new(
+                    batch.catalog.into(),
+                    batch.database_sequence_number,
+                )),
+                WalOp::Noop(details) => Self::Noop(details),
+            }
+        }
+    }
+
+    impl From<CatalogBatch> for crate::CatalogBatch {
+        fn from(batch: CatalogBatch) -> Self {
+            Self {
+                database_id: batch.database_id,
+                database_name: batch.database_name,
+                time_ns: batch.time_ns,
+                ops: batch.ops.into_iter().map(Into::into).collect(),
+            }
+        }
+    }
+
+    impl From<CatalogOp> for crate::CatalogOp {
+        fn from(op: CatalogOp) -> Self {
+            match op {
+                CatalogOp::CreateDatabase(def) => Self::CreateDatabase(def),
+                CatalogOp::CreateTable(def) => Self::CreateTable(crate::WalTableDefinition {
+                    database_id: def.database_id,
+                    database_name: def.database_name,
+                    table_name: def.table_name,
+                    table_id: def.table_id,
+                    field_definitions: def.field_definitions,
+                    key: def.key,
+                    explicit_series_key: false,
+                }),
+                CatalogOp::AddFields(def) => Self::AddFields(def),
+                CatalogOp::CreateDistinctCache(def) => Self::CreateDistinctCache(def),
+                CatalogOp::DeleteDistinctCache(def) => Self::DeleteDistinctCache(def),
+                CatalogOp::CreateLastCache(def) => Self::CreateLastCache(def),
+                CatalogOp::DeleteLastCache(def) => Self::DeleteLastCache(def),
+                CatalogOp::DeleteDatabase(def) => Self::DeleteDatabase(def),
+                CatalogOp::DeleteTable(def) => Self::DeleteTable(def),
+                CatalogOp::CreateTrigger(def) => Self::CreateTrigger(def),
+                CatalogOp::DeleteTrigger(def) => Self::DeleteTrigger(def),
+                CatalogOp::EnableTrigger(def) => Self::EnableTrigger(def),
+                CatalogOp::DisableTrigger(def) => Self::DisableTrigger(def),
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        Field, FieldData, Row, TableChunk, TableChunks, WalFileSequenceNumber, WalOp, WriteBatch,
+        CatalogBatch, CatalogOp, Field, FieldData, FieldDataType, FieldDefinition,
+        OrderedCatalogBatch, Row, TableChunk, TableChunks, WalFileSequenceNumber, WalOp,
+        WalTableDefinition, WriteBatch,
     };
     use influxdb3_id::{ColumnId, DbId, SerdeVecMap, TableId};
 
@@ -140,4 +297,98 @@ mod tests {
 
         assert_eq!(contents, deserialized);
     }
+
+    #[test]
+    fn test_serialize_deserialize_default_tags() {
+        let db_id = DbId::from(0);
+        let contents = WalContents {
+            persist_timestamp_ms: 10,
+            min_timestamp_ns: 0,
+            max_timestamp_ns: 10,
+            wal_file_number: WalFileSequenceNumber::new(1),
+            ops: vec![crate::create::catalog_batch_op(
+                db_id,
+                "foo",
+                0,
+                [crate::create::set_default_tags_op(
+                    db_id,
+                    "foo",
+                    [("environment".into(), "prod".into())],
+                )],
+                1,
+            )],
+            snapshot: None,
+        };
+
+        let bytes = serialize_to_file_bytes(&contents).unwrap();
+        let deserialized = verify_file_type_and_deserialize(Bytes::from(bytes)).unwrap();
+
+        assert_eq!(contents, deserialized);
+    }
+
+    #[test]
+    fn test_deserialize_v1() {
+        let db_id = DbId::from(0);
+        let field_definitions = vec![
+            FieldDefinition::new(ColumnId::from(0), "host", FieldDataType::Tag),
+            FieldDefinition::new(ColumnId::from(1), "time", FieldDataType::Timestamp),
+        ];
+        // a file written before tables recorded whether their series key was declared:
+        let contents = v1::WalContents {
+            persist_timestamp_ms: 10,
+            min_timestamp_ns: 0,
+            max_timestamp_ns: 10,
+            wal_file_number: WalFileSequenceNumber::new(1),
+            ops: vec![v1::WalOp::Catalog(v1::OrderedCatalogBatch {
+                catalog: v1::CatalogBatch {
+                    database_id: db_id,
+                    database_name: "foo".into(),
+                    time_ns: 0,
+                    ops: vec![v1::CatalogOp::CreateTable(v1::WalTableDefinition {
+                        database_id: db_id,
+                        database_name: "foo".into(),
+                        table_name: "cpu".into(),
+                        table_id: TableId::from(0),
+                        field_definitions: field_definitions.clone(),
+                        key: vec![ColumnId::from(0)],
+                    })],
+                },
+                database_sequence_number: 1,
+            })],
+            snapshot: None,
+        };
+
+        let bytes = serialize_with_identifier(FILE_TYPE_IDENTIFIER_V1, &contents).unwrap();
+        let deserialized = verify_file_type_and_deserialize(Bytes::from(bytes)).unwrap();
+
+        let expected = WalContents {
+            persist_timestamp_ms: 10,
+            min_timestamp_ns: 0,
+            max_timestamp_ns: 10,
+            wal_file_number: WalFileSequenceNumber::new(1),
+            ops: vec![WalOp::Catalog(OrderedCatalogBatch::new(
+                CatalogBatch {
+                    database_id: db_id,
+                    database_name: "foo".into(),
+                    time_ns: 0,
+                    ops: vec![CatalogOp::CreateTable(WalTableDefinition {
+                        database_id: db_id,
+                        database_name: "foo".into(),
+                        table_name: "cpu".into(),
+                        table_id: TableId::from(0),
+                        field_definitions,
+                        key: vec![ColumnId::from(0)],
+                        explicit_series_key: false,
+                    })],
+                },
+                1,
+            ))],
+            snapshot: None,
+        };
+        assert_eq!(expected, deserialized);
+
+        // when written again, the file has the current version:
+        let bytes = serialize_to_file_bytes(&expected).unwrap();
+        assert_eq!(&bytes[..FILE_TYPE_IDENTIFIER.len()], FILE_TYPE_IDENTIFIER);
+    }
 }
@@ -0,0 +1,119 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use data_types::NamespaceName;
+use influxdb3_catalog::catalog::Catalog;
+use influxdb3_write::{
+    Precision,
+    write_buffer::validator::{Scratch, WithCatalog, WriteValidator},
+};
+use iox_time::Time;
+
+const LINE_COUNT: usize = 100_000;
+
+/// Counts the allocations made by the benchmark, so that the allocations of a write can be
+/// reported along with its duration
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn line_protocol() -> String {
+    (0..LINE_COUNT)
+        .map(|i| {
+            format!(
+                "cpu,host=h{host},region=r{region} usage={i}.5,idle={i}i {i}\n",
+                host = i % 100,
+                region = i % 5,
+            )
+        })
+        .collect()
+}
+
+/// A validator for a database whose table was already created by a write of the same lines,
+/// so the benchmarked writes do not change the catalog
+fn validator(lp: &str) -> WriteValidator<WithCatalog> {
+    let catalog = Arc::new(Catalog::new(
+        "sample-host-id".into(),
+        "sample-instance-id".into(),
+    ));
+    let namespace = NamespaceName::new("test").unwrap();
+    let mut validator = WriteValidator::initialize(namespace, catalog, 0).unwrap();
+    validator
+        .parse_lines_and_update_schema_reusing(lp, false, ingest_time(), Precision::Nanosecond)
+        .unwrap();
+    validator
+}
+
+fn ingest_time() -> Time {
+    Time::from_timestamp_nanos(0)
+}
+
+fn without_scratch(validator: &mut WriteValidator<WithCatalog>, lp: &str) {
+    validator
+        .parse_lines_and_update_schema_reusing(lp, false, ingest_time(), Precision::Nanosecond)
+        .unwrap();
+}
+
+fn with_scratch(validator: &mut WriteValidator<WithCatalog>, scratch: &mut Scratch, lp: &str) {
+    validator
+        .parse_lines_with_scratch(scratch, lp, false, ingest_time(), Precision::Nanosecond)
+        .unwrap();
+}
+
+/// The number of allocations made by `f`
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let lp = line_protocol();
+    let mut validator = validator(&lp);
+    let mut scratch = Scratch::new();
+    with_scratch(&mut validator, &mut scratch, &lp);
+
+    let allocations_without_scratch = allocations(|| without_scratch(&mut validator, &lp));
+    let allocations_with_scratch = allocations(|| with_scratch(&mut validator, &mut scratch, &lp));
+    println!(
+        "allocations to validate {LINE_COUNT} lines: {allocations_without_scratch} without a \
+        scratch buffer, {allocations_with_scratch} with a scratch buffer"
+    );
+
+    let mut group = c.benchmark_group("validate_lines");
+    group.bench_function(BenchmarkId::new("without_scratch", LINE_COUNT), |b| {
+        b.iter(|| without_scratch(&mut validator, &lp))
+    });
+    group.bench_function(BenchmarkId::new("with_scratch", LINE_COUNT), |b| {
+        b.iter(|| with_scratch(&mut validator, &mut scratch, &lp))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
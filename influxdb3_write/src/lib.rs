@@ -74,12 +74,20 @@ pub trait DatabaseManager: Debug + Send + Sync + 'static {
         table: String,
         tags: Vec<String>,
         fields: Vec<(String, String)>,
+        explicit_series_key: bool,
     ) -> Result<(), write_buffer::Error>;
     async fn soft_delete_table(
         &self,
         db_name: String,
         table_name: String,
     ) -> Result<(), write_buffer::Error>;
+    /// Replace the default tags of a database, which are added to the lines written to it that
+    /// do not set them
+    async fn set_default_tags(
+        &self,
+        db_name: String,
+        tags: Vec<(String, String)>,
+    ) -> Result<(), write_buffer::Error>;
 }
 
 /// The buffer is for buffering data in memory and in the wal before it is persisted as parquet files in storage.
@@ -192,13 +200,22 @@ pub trait LastCacheManager: Debug + Send + Sync + 'static {
 
 /// A single write request can have many lines in it. A writer can request to accept all lines that are valid, while
 /// returning an error for any invalid lines. This is the error information for a single invalid line.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct WriteLineError {
     pub original_line: String,
     pub line_number: usize,
     pub error_message: String,
 }
 
+/// A line that was accepted by the write validator, but with a condition that the writer may
+/// want to know about, e.g., a timestamp that looks to be from a skewed client clock.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteLineWarning {
+    pub original_line: String,
+    pub line_number: usize,
+    pub warning_message: String,
+}
+
 /// A write that has been validated against the catalog schema, written to the WAL (if configured), and buffered in
 /// memory. This is the summary information for the write along with any errors that were encountered.
 #[derive(Debug)]
@@ -370,10 +387,10 @@ impl ParquetFile {
 /// Guess precision based off of a given timestamp.
 // Note that this will fail in June 2128, but that's not our problem
 pub(crate) fn guess_precision(timestamp: i64) -> Precision {
-    const NANO_SECS_PER_SEC: i64 = 1_000_000_000;
+    const NANO_SECS_PER_SEC: u64 = 1_000_000_000;
     // Get the absolute value of the timestamp so we can work with negative
-    // numbers
-    let val = timestamp.abs() / NANO_SECS_PER_SEC;
+    // numbers, as unsigned so that it does not overflow for `i64::MIN`
+    let val = timestamp.unsigned_abs() / NANO_SECS_PER_SEC;
 
     if val < 5 {
         // If the time sent to us is in seconds then this will be a number less than
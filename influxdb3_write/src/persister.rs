@@ -527,6 +527,7 @@ mod tests {
                         data_type: FieldDataType::String,
                     }],
                     key: vec![num.into()],
+                    explicit_series_key: false,
                 })],
             });
         };
@@ -11,7 +11,7 @@ pub mod validator;
 use crate::persister::Persister;
 use crate::write_buffer::persisted_files::PersistedFiles;
 use crate::write_buffer::queryable_buffer::QueryableBuffer;
-use crate::write_buffer::validator::WriteValidator;
+use crate::write_buffer::validator::{DEFAULT_MAX_LINES, MaxLinesPolicy, WriteValidator};
 use crate::{
     BufferedWriteRequest, Bufferer, ChunkContainer, ChunkFilter, DistinctCacheManager,
     LastCacheManager, ParquetFile, PersistedSnapshot, Precision, WriteBuffer, WriteLineError,
@@ -40,7 +40,7 @@ use influxdb3_wal::{
     LastCacheDelete, LastCacheSize, Wal, WalConfig, WalFileNotifier, WalOp,
 };
 use influxdb3_wal::{CatalogOp::CreateLastCache, DeleteTableDefinition};
-use influxdb3_wal::{DatabaseDefinition, FieldDefinition};
+use influxdb3_wal::{DatabaseDefinition, DefaultTagsDefinition, FieldDefinition};
 use influxdb3_wal::{DeleteDatabaseDefinition, object_store::WalObjectStore};
 use iox_query::chunk_statistics::{NoColumnRanges, create_chunk_statistics};
 use iox_query::{QueryChunk, exec::SessionContextIOxExt};
@@ -62,6 +62,41 @@ pub enum Error {
     #[error("parsing for line protocol failed")]
     ParseError(WriteLineError),
 
+    #[error("write of {size} bytes exceeds the limit of {limit} bytes")]
+    BatchTooLarge { size: usize, limit: usize },
+
+    #[error("write has more than the maximum of {limit} lines")]
+    TooManyLines { limit: usize },
+
+    #[error("write would make {count} catalog changes, which exceeds the limit of {limit}")]
+    TooManyCatalogOps { count: usize, limit: usize },
+
+    #[error("row for table {table_id} references column {column_id}, which is not in the catalog")]
+    ColumnNotInCatalog {
+        table_id: TableId,
+        column_id: ColumnId,
+    },
+
+    #[error(
+        "column '{column_name}' of table '{table_name}' resolved to {actual}, but was expected \
+        to be {expected}"
+    )]
+    ColumnIdMismatch {
+        table_name: String,
+        column_name: String,
+        expected: ColumnId,
+        actual: ColumnId,
+    },
+
+    #[error(
+        "write to a frozen schema conflicts with a change made to table '{table_name}' since \
+        the schema was frozen"
+    )]
+    FrozenSchemaConflict { table_name: String },
+
+    #[error("error serializing validated lines: {0}")]
+    ValidatedLinesSerialization(#[from] bitcode::Error),
+
     #[error("column type mismatch for column {name}: existing: {existing:?}, new: {new:?}")]
     ColumnTypeMismatch {
         name: String,
@@ -285,6 +320,7 @@ impl WriteBufferImpl {
             self.catalog(),
             ingest_time.timestamp_nanos(),
         )?
+        .with_max_lines(DEFAULT_MAX_LINES, MaxLinesPolicy::Reject)
         .parse_lines_and_update_schema(lp, accept_partial, ingest_time, precision)?
         .convert_lines_to_buffer(self.wal_config.gen1_duration);
 
@@ -794,6 +830,7 @@ impl DatabaseManager for WriteBufferImpl {
         table: String,
         tags: Vec<String>,
         fields: Vec<(String, String)>,
+        explicit_series_key: bool,
     ) -> Result<(), self::Error> {
         // get the database schema or create it if it does not yet exist:
         let (db_id, db_schema) = match self.catalog.db_id_and_schema(&db) {
@@ -859,6 +896,7 @@ impl DatabaseManager for WriteBufferImpl {
             table_id,
             field_definitions,
             key,
+            explicit_series_key,
         };
 
         let catalog_batch = CatalogBatch {
@@ -928,6 +966,39 @@ impl DatabaseManager for WriteBufferImpl {
         );
         Ok(())
     }
+
+    async fn set_default_tags(
+        &self,
+        db_name: String,
+        tags: Vec<(String, String)>,
+    ) -> crate::Result<(), self::Error> {
+        let (db_id, db_schema) = self.catalog.db_id_and_schema(&db_name).ok_or_else(|| {
+            self::Error::DatabaseNotFound {
+                db_name: db_name.to_owned(),
+            }
+        })?;
+
+        let catalog_batch = CatalogBatch {
+            time_ns: self.time_provider.now().timestamp_nanos(),
+            database_id: db_id,
+            database_name: Arc::clone(&db_schema.name),
+            ops: vec![CatalogOp::SetDefaultTags(DefaultTagsDefinition {
+                database_id: db_id,
+                database_name: Arc::clone(&db_schema.name),
+                tags: tags
+                    .into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            })],
+        };
+        if let Some(catalog_batch) = self.catalog.apply_catalog_batch(&catalog_batch)? {
+            self.wal
+                .write_ops(vec![WalOp::Catalog(catalog_batch)])
+                .await?;
+        }
+        debug!(db_id = ?db_id, name = ?&db_schema.name, "successfully set default tags");
+        Ok(())
+    }
 }
 
 impl WriteBuffer for WriteBufferImpl {}
@@ -2453,6 +2524,65 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_set_default_tags() {
+        let start_time = Time::from_rfc3339("2024-11-14T11:00:00+00:00").unwrap();
+        let test_store = Arc::new(InMemory::new());
+        let wal_config = WalConfig {
+            gen1_duration: Gen1Duration::new_1m(),
+            max_write_buffer_size: 100,
+            flush_interval: Duration::from_millis(10),
+            snapshot_size: 1,
+        };
+        let (write_buffer, ctx, _) =
+            setup_cache_optional(start_time, test_store, wal_config, false).await;
+
+        let result = write_buffer
+            .set_default_tags(
+                "foo".to_string(),
+                vec![("environment".to_string(), "prod".to_string())],
+            )
+            .await;
+        assert!(matches!(result, Err(Error::DatabaseNotFound { .. })));
+
+        write_buffer
+            .create_database("foo".to_string())
+            .await
+            .unwrap();
+        write_buffer
+            .set_default_tags(
+                "foo".to_string(),
+                vec![("environment".to_string(), "prod".to_string())],
+            )
+            .await
+            .unwrap();
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,host=a usage=0.5 1\n\
+                cpu,host=b,environment=dev usage=0.7 2\n",
+                start_time,
+                false,
+                Precision::Nanosecond,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let batches = get_table_batches_from_query_buffer(&write_buffer, "foo", "cpu", &ctx).await;
+        assert_batches_sorted_eq!(
+            [
+                "+-------------+------+--------------------------------+-------+",
+                "| environment | host | time                           | usage |",
+                "+-------------+------+--------------------------------+-------+",
+                "| dev         | b    | 1970-01-01T00:00:00.000000002Z | 0.7   |",
+                "| prod        | a    | 1970-01-01T00:00:00.000000001Z | 0.5   |",
+                "+-------------+------+--------------------------------+-------+",
+            ],
+            &batches
+        );
+    }
+
     #[tokio::test]
     async fn test_delete_table() {
         let start_time = Time::from_rfc3339("2024-11-14T11:00:00+00:00").unwrap();
@@ -2913,6 +3043,7 @@ mod tests {
                 "cpu".to_string(),
                 vec!["region".to_string(), "host".to_string()],
                 vec![("usage".to_string(), "float64".to_string())],
+                false,
             )
             .await
             .unwrap();
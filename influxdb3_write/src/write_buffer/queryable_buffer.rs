@@ -564,6 +564,7 @@ impl BufferState {
                             CatalogOp::DeleteTrigger(_) => {}
                             CatalogOp::EnableTrigger(_) => {}
                             CatalogOp::DisableTrigger(_) => {}
+                            CatalogOp::SetDefaultTags(_) => {}
                         }
                     }
                 }
@@ -453,6 +453,25 @@ impl MutableTableChunk {
     }
 }
 
+/// Build a [`RecordBatch`] from the given rows, using the schema of the table they belong to
+///
+/// Columns for which none of the rows have values are filled with nulls.
+pub(crate) fn record_batch_from_rows<'a>(
+    table_def: Arc<TableDefinition>,
+    rows: impl IntoIterator<Item = &'a Row>,
+) -> Result<RecordBatch> {
+    let mut chunk = MutableTableChunk {
+        timestamp_min: i64::MAX,
+        timestamp_max: i64::MIN,
+        data: Default::default(),
+        row_count: 0,
+    };
+    for row in rows {
+        chunk.add_rows(std::slice::from_ref(row));
+    }
+    chunk.record_batch(table_def)
+}
+
 fn array_ref_nulls_for_type(data_type: InfluxColumnType, len: usize) -> ArrayRef {
     match data_type {
         InfluxColumnType::Field(InfluxFieldType::Boolean) => {
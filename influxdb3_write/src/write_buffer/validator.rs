@@ -1,50 +1,533 @@
-use std::{borrow::Cow, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use crate::{Precision, WriteLineError, write_buffer::Result};
+use crate::{Precision, WriteLineError, WriteLineWarning, write_buffer::Result};
+use arrow::record_batch::RecordBatch;
 use data_types::{NamespaceName, Timestamp};
-use indexmap::IndexMap;
+use hashbrown::{HashMap, HashSet};
+use indexmap::{IndexMap, IndexSet};
 use influxdb3_catalog::catalog::{
-    Catalog, DatabaseSchema, TableDefinition, influx_column_type_from_field_value,
+    Catalog, CatalogSequenceNumber, DatabaseSchema, TableDefinition,
+    influx_column_type_from_field_value,
 };
+use observability_deps::tracing::error;
 
-use influxdb_line_protocol::{ParsedLine, parse_lines};
-use influxdb3_id::{ColumnId, TableId};
+use influxdb_line_protocol::{FieldValue, ParsedLine, parse_lines};
+use influxdb3_id::{ColumnId, DbId, TableId};
 use influxdb3_wal::{
     CatalogBatch, CatalogOp, Field, FieldAdditions, FieldData, FieldDefinition, Gen1Duration,
     OrderedCatalogBatch, Row, TableChunks, WriteBatch,
 };
-use iox_time::Time;
-use schema::{InfluxColumnType, TIME_COLUMN_NAME};
+use iox_time::{Time, TimeProvider};
+use parking_lot::Mutex;
+use regex::Regex;
+use schema::{InfluxColumnType, InfluxFieldType, TIME_COLUMN_NAME};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
 
-use super::Error;
+use super::{Error, table_buffer::record_batch_from_rows};
 
 /// Type state for the [`WriteValidator`] after it has been initialized
 /// with the catalog.
 #[derive(Debug)]
 pub struct WithCatalog {
-    catalog: Arc<Catalog>,
+    /// The catalog to apply schema changes to, which is `None` for a validator initialized
+    /// with a read-only snapshot of the database schema
+    catalog: Option<Arc<Catalog>>,
+    /// The catalog's sequence number when `db_schema` was fetched
+    catalog_sequence: CatalogSequenceNumber,
     db_schema: Arc<DatabaseSchema>,
     time_now_ns: i64,
+    config: ValidatorConfig,
+}
+
+/// Optional checks and behaviours of the [`WriteValidator`]
+///
+/// These are set using the `with_*` methods on the [`WriteValidator`] after it has been
+/// initialized, and all default to the validator's original behaviour.
+#[derive(Debug, Default)]
+struct ValidatorConfig {
+    /// How far ahead of the ingest time a timestamp can be before it is considered skewed
+    max_future_skew: Option<(Duration, ClockSkewPolicy)>,
+    /// Whether to sum the size of values written to each column
+    column_size_accounting: bool,
+    /// How to handle measurement names that are not safe to use in file paths
+    measurement_name_policy: MeasurementNamePolicy,
+    /// How to fold the case of measurement names
+    measurement_case_policy: MeasurementCasePolicy,
+    /// The maximum size in bytes of line protocol accepted in a single write
+    max_batch_bytes: Option<usize>,
+    /// Whether to produce the encoded series key for each line
+    series_keys: bool,
+    /// Tags added by the server to every line
+    injected_tags: Vec<(Arc<str>, Arc<str>)>,
+    /// The default tags of the database, copied from its schema whenever it is fetched
+    default_tags: Vec<(Arc<str>, Arc<str>)>,
+    /// The maximum number of catalog ops a single write can produce
+    max_catalog_ops: Option<usize>,
+    /// Whether to produce a hash of each row for deduplication
+    row_hashes: bool,
+    /// Whether to accept integral float values for integer columns
+    integral_floats_as_integers: bool,
+    /// How to handle float values for integer columns
+    float_to_int_policy: FloatToIntPolicy,
+    /// The base timestamp in nanoseconds that line timestamps are deltas from, if timestamps
+    /// are delta-encoded
+    timestamp_base_ns: Option<i64>,
+    /// Called for each table created by a write
+    on_table_created: Option<TableCreatedCallback>,
+    /// Whether to keep the original text of each valid line
+    original_lines: bool,
+    /// Whether the validator has a read-only snapshot of the schema, so lines that would
+    /// change the schema are rejected
+    read_only: bool,
+    /// How to handle lines that have no tags or fields
+    empty_row_policy: EmptyRowPolicy,
+    /// The maximum span between the earliest and latest timestamps in a single write
+    max_timestamp_span: Option<(Duration, TimestampSpanPolicy)>,
+    /// The maximum number of distinct values for tags with the given names in a single write
+    tag_cardinality_budgets: HashMap<Arc<str>, usize>,
+    /// Whether to verify that the columns of all rows exist in the catalog once it is updated
+    verify_column_ids: bool,
+    /// Whether to check that the validator's copy of the schema matches the catalog once it is
+    /// updated
+    check_schema_consistency: bool,
+    /// How to handle lines that are not compatible with the existing schema
+    incompatible_line_mode: IncompatibleLineMode,
+    /// The timestamp in nanoseconds that line timestamps must be greater than
+    min_timestamp_ns: Option<i64>,
+    /// Whether the schema is frozen at the version the validator was initialized with, so
+    /// changes made to the catalog by other writers are not seen
+    frozen_schema: bool,
+    /// Whether to skip creating columns for tags that are empty in every line of a write
+    skip_empty_tags: bool,
+    /// The maximum size in bytes of a string field value
+    max_string_field_bytes: Option<(usize, StringLengthPolicy)>,
+    /// The IDs that columns are expected to resolve to, by table and column name, when
+    /// replaying writes
+    expected_column_ids: HashMap<Arc<str>, HashMap<Arc<str>, ColumnId>>,
+    /// Called with each row and the definition of its table, to check custom rules
+    row_validator: Option<RowValidator>,
+    /// How to handle lines for existing tables that have none of the series key tags
+    series_key_missing_policy: SeriesKeyMissingPolicy,
+    /// Whether the lines are written by a client that declares the series key of each table
+    /// explicitly
+    explicit_series_keys: bool,
+    /// Whether to time the phases of validating a write
+    phase_durations: bool,
+    /// The maximum number of lines accepted in a single write
+    max_lines: Option<(usize, MaxLinesPolicy)>,
+    /// The pattern that the names of new columns must match
+    column_name_pattern: Option<Regex>,
+    /// Whether to record the precision applied to the timestamp of each line
+    effective_precisions: bool,
+    /// Splits the measurement name of each line into the name of its table and tags
+    measurement_splitter: Option<MeasurementSplitter>,
+    /// The types to create columns with, by column name, instead of inferring them from the
+    /// first value written
+    column_type_hints: HashMap<Arc<str>, InfluxColumnType>,
+}
+
+/// A callback invoked with the ID and name of each table created by a write
+///
+/// This is behind a mutex so that it can be called while the config is shared across lines.
+struct TableCreatedCallback(Mutex<Box<dyn FnMut(TableId, &str) + Send>>);
+
+impl std::fmt::Debug for TableCreatedCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TableCreatedCallback")
+            .finish_non_exhaustive()
+    }
+}
+
+/// A function that checks each row against custom rules, returning a message for rows that
+/// are rejected
+type RowValidatorFn = dyn Fn(&Row, &TableDefinition) -> Result<(), String> + Send + Sync;
+
+struct RowValidator(Box<RowValidatorFn>);
+
+impl std::fmt::Debug for RowValidator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("RowValidator").finish_non_exhaustive()
+    }
+}
+
+/// The name of the table and the tags that a measurement name is split into
+type SplitMeasurement = (Arc<str>, Vec<(Arc<str>, Arc<str>)>);
+
+/// A function that splits a measurement name into the name of a table and tags
+type MeasurementSplitterFn = dyn Fn(&str) -> SplitMeasurement + Send + Sync;
+
+struct MeasurementSplitter(Box<MeasurementSplitterFn>);
+
+impl std::fmt::Debug for MeasurementSplitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("MeasurementSplitter").finish_non_exhaustive()
+    }
+}
+
+/// What to do with a line whose timestamp is further ahead of the ingest time than the
+/// configured maximum skew
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClockSkewPolicy {
+    /// Accept the line, but produce a [`WriteLineWarning`]
+    #[default]
+    Warn,
+    /// Reject the line with a [`WriteLineError`]
+    Reject,
+}
+
+/// What to do with a line whose timestamp makes the span of timestamps in the write exceed
+/// the configured maximum
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampSpanPolicy {
+    /// Accept the line, but produce a [`WriteLineWarning`], once per write
+    #[default]
+    Warn,
+    /// Reject the line with a [`WriteLineError`]
+    Reject,
+}
+
+/// What to do with a measurement name that contains characters that are not safe to use in
+/// object store paths or Parquet file names, e.g., path separators, commas, or control
+/// characters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MeasurementNamePolicy {
+    /// Accept the measurement name as is
+    #[default]
+    Allow,
+    /// Reject the line with a [`WriteLineError`]
+    Reject,
+    /// Replace each unsafe character with an underscore, and produce a [`WriteLineWarning`]
+    Sanitize,
+}
+
+/// How the case of measurement names is normalized before they are resolved to a table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MeasurementCasePolicy {
+    /// Use the measurement name as is, so names that differ only in case are different tables
+    #[default]
+    Preserve,
+    /// Convert the measurement name to lowercase, so names that differ only in case are the
+    /// same table, whose name is the lowercase form
+    Lowercase,
+}
+
+/// What to do with a line that is not compatible with the existing schema, i.e., that would
+/// create a table, add a column, or has a value of the wrong type for a column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IncompatibleLineMode {
+    /// Update the schema for lines that create tables or add columns, and reject lines with
+    /// a [`WriteLineError`] for type mismatches
+    #[default]
+    Strict,
+    /// Silently drop the line, without updating the schema, and count it in the
+    /// `dropped_incompatible_count`
+    DropIncompatible,
+}
+
+/// What to do with a line that has no tags or fields left after any transforms, so its row
+/// would only have a timestamp
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyRowPolicy {
+    /// Reject the line with a [`WriteLineError`]
+    #[default]
+    Reject,
+    /// Skip the line, and produce a [`WriteLineWarning`]
+    Skip,
+}
+
+/// What to do with a float value written to an existing integer column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FloatToIntPolicy {
+    /// Reject the line with a [`WriteLineError`], unless the value is integral and integral
+    /// floats are accepted
+    #[default]
+    Error,
+    /// Truncate the value towards zero, and produce a [`WriteLineWarning`]
+    Truncate,
+    /// Round the value to the nearest integer, with halves rounded away from zero, and produce a
+    /// [`WriteLineWarning`]
+    Round,
+}
+
+/// What to do with a string field value that exceeds the maximum size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StringLengthPolicy {
+    /// Reject the line with a [`WriteLineError`]
+    #[default]
+    Reject,
+    /// Truncate the value to the maximum size, at a character boundary, and produce a
+    /// [`WriteLineWarning`]
+    Truncate,
+}
+
+/// A maximum number of lines per write that bounds the work done for a single request, for
+/// use with [`WriteValidator::with_max_lines`]
+pub const DEFAULT_MAX_LINES: usize = 10_000_000;
+
+/// What to do with a write that has more lines than the maximum
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MaxLinesPolicy {
+    /// Reject the write with [`Error::TooManyLines`]
+    #[default]
+    Reject,
+    /// Validate the lines up to the maximum, and ignore the rest, which is reported by
+    /// `lines_truncated` in the [`ValidatedLines`]
+    Truncate,
+}
+
+/// What to do with a line for an existing table that has none of the tags in the table's
+/// series key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SeriesKeyMissingPolicy {
+    /// Accept the line as is, without values for the series key tags
+    #[default]
+    Allow,
+    /// Reject the line with a [`WriteLineError`]
+    Reject,
+    /// Accept the line as a new series, with an empty string for each series key tag
+    EmptyDefaults,
+}
+
+/// A source of the ingest time for a write, in nanoseconds since the epoch
+///
+/// The ingest time is read once per write, and is used as the timestamp of lines that do not
+/// have one, and as the reference point for clock skew checks.
+pub trait TimeSource {
+    /// The ingest time, in nanoseconds since the epoch
+    fn now_ns(&self) -> i64;
+}
+
+impl TimeSource for Time {
+    fn now_ns(&self) -> i64 {
+        self.timestamp_nanos()
+    }
+}
+
+impl TimeSource for i64 {
+    fn now_ns(&self) -> i64 {
+        *self
+    }
+}
+
+impl<T: TimeSource + ?Sized> TimeSource for &T {
+    fn now_ns(&self) -> i64 {
+        (**self).now_ns()
+    }
+}
+
+impl TimeSource for Arc<dyn TimeProvider> {
+    fn now_ns(&self) -> i64 {
+        self.now().timestamp_nanos()
+    }
+}
+
+/// Buffers that are reused across the lines of writes, to avoid allocating them for every
+/// line, see [`WriteValidator::parse_lines_with_scratch`]
+///
+/// The buffers are empty between lines, so only their allocations are kept.
+#[derive(Debug, Default)]
+pub struct Scratch {
+    /// The tags to be written for a line
+    tags: Vec<LineTag<'static>>,
+    /// The new values of tags with a cardinality budget for a line
+    new_tag_values: Vec<(ColumnId, &'static str)>,
+}
+
+impl Scratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Clear a buffer, and reuse its allocation for a buffer of another type of the same size and
+/// alignment, which is how buffers of values that borrow from a line are kept across lines
+fn reuse_vec<T, U>(mut buffer: Vec<T>) -> Vec<U> {
+    buffer.clear();
+    buffer.into_iter().map(|_| unreachable!()).collect()
+}
+
+/// State that is accumulated across all lines of a write as they are validated
+#[derive(Debug, Default)]
+struct BatchState {
+    warnings: Vec<WriteLineWarning>,
+    per_column_bytes: HashMap<ColumnId, u64>,
+    /// Whether the lines are only being checked, so the tables they create are discarded
+    check_only: bool,
+    /// The earliest and latest timestamps of the lines so far, if the timestamp span is checked
+    time_range: Option<(i64, i64)>,
+    /// Whether a warning has been produced for the timestamp span
+    time_span_warned: bool,
+    /// The distinct values seen for each tag column that has a cardinality budget
+    tag_values: HashMap<ColumnId, HashSet<String>>,
+    /// Number of lines dropped because they were not compatible with the schema
+    dropped_incompatible_count: usize,
+    /// The tags that have a non-empty value in at least one line of the write, by table name,
+    /// if tags that are always empty are skipped
+    non_empty_tags: Option<HashMap<String, HashSet<String>>>,
+    /// The tags that have been skipped because they are always empty, by table name
+    skipped_empty_tags: HashSet<(String, String)>,
+    /// The buffers reused across lines
+    scratch: Scratch,
+    /// The state produced by the line being validated
+    line: LineState,
+}
+
+impl BatchState {
+    /// Apply the state produced by a line to the batch, once the line is accepted
+    fn accept_line(&mut self) {
+        self.warnings.append(&mut self.line.warnings);
+        if let Some(time_range) = self.line.time_range.take() {
+            self.time_range = Some(time_range);
+        }
+        self.time_span_warned |= std::mem::take(&mut self.line.time_span_warned);
+        self.skipped_empty_tags
+            .extend(self.line.skipped_empty_tags.drain(..));
+    }
+}
+
+/// State produced by validating a line, which is only applied to the [`BatchState`] if the
+/// line is accepted, so a line that is rejected or dropped by a later check leaves no trace
+#[derive(Debug, Default)]
+struct LineState {
+    /// Any warnings for the line
+    warnings: Vec<WriteLineWarning>,
+    /// The earliest and latest timestamps of the lines so far, including the line, if the
+    /// timestamp span is checked
+    time_range: Option<(i64, i64)>,
+    /// Whether the line produced the warning for the timestamp span
+    time_span_warned: bool,
+    /// The tags skipped for the first time by the line because they are always empty, by
+    /// table name
+    skipped_empty_tags: Vec<(String, String)>,
+}
+
+impl LineState {
+    fn clear(&mut self) {
+        self.warnings.clear();
+        self.time_range = None;
+        self.time_span_warned = false;
+        self.skipped_empty_tags.clear();
+    }
+}
+
+/// The result of checking lines of line protocol with [`WriteValidator::check_lines`]
+#[derive(Debug, Default)]
+pub struct CheckedLines {
+    /// Number of valid lines
+    pub line_count: usize,
+    /// Number of fields in valid lines
+    pub field_count: usize,
+    /// Number of index columns in valid lines, whether tags (v1) or series keys (v3)
+    pub index_count: usize,
+    /// Any errors that occurred while checking the lines
+    pub errors: Vec<WriteLineError>,
+    /// Any warnings for lines that would be accepted
+    pub warnings: Vec<WriteLineWarning>,
+    /// Number of lines that would be dropped because they are not compatible with the schema
+    pub dropped_incompatible_count: usize,
 }
 
 /// Type state for the [`WriteValidator`] after it has parsed v1 or v3
 /// line protocol.
 #[derive(Debug)]
 pub struct LinesParsed {
-    catalog: WithCatalog,
+    /// The schema of the database that the write was validated against
+    before_schema: Arc<DatabaseSchema>,
+    /// The schema of the database with any changes made by the write applied
+    validated_schema: Arc<DatabaseSchema>,
     lines: Vec<QualifiedLine>,
     bytes: u64,
     catalog_batch: Option<OrderedCatalogBatch>,
     errors: Vec<WriteLineError>,
+    /// Number of the errors that are for lines that could not be parsed
+    parse_error_count: usize,
+    warnings: Vec<WriteLineWarning>,
+    per_column_bytes: HashMap<ColumnId, u64>,
+    dropped_incompatible_count: usize,
+    /// The time spent parsing and applying the write, if phase durations were enabled
+    phase_durations: Option<PhaseDurations>,
+    /// Whether lines past the maximum number of lines were ignored
+    lines_truncated: bool,
 }
 
 impl LinesParsed {
+    /// The schema of the database with any tables and columns added by the write
+    ///
+    /// This reflects the changes made by the write without fetching the schema from the catalog
+    /// again.
+    pub fn validated_schema(&self) -> &Arc<DatabaseSchema> {
+        &self.validated_schema
+    }
+
+    /// The schema of the database that the write was validated against, before any changes
+    /// made by the write
+    pub fn before_schema(&self) -> &Arc<DatabaseSchema> {
+        &self.before_schema
+    }
+
+    /// The tables and columns added by the write, see [`schema_diff`]
+    pub fn schema_diff(&self) -> SchemaDiff {
+        schema_diff(&self.before_schema, &self.validated_schema)
+    }
+
     /// Convert this set of parsed and qualified lines into a set of rows
     ///
     /// This is useful for testing when you need to use the write validator to parse line protocol
     /// and get the raw row data for the WAL.
     pub fn to_rows(self) -> Vec<Row> {
-        self.lines.into_iter().map(|line| line.row).collect()
+        self.into_row_iter().collect()
+    }
+
+    /// Convert this set of parsed and qualified lines into an iterator over their rows, in the
+    /// order the lines were written
+    ///
+    /// Unlike [`to_rows`][Self::to_rows], this does not collect the rows, so they can be
+    /// streamed into storage one at a time. Each row is moved out of the lines as it is
+    /// produced.
+    pub fn into_row_iter(self) -> impl Iterator<Item = Row> {
+        self.lines.into_iter().map(|line| line.row)
+    }
+
+    /// Convert this set of parsed and qualified lines into rows grouped by table, along with
+    /// the catalog batch for any schema changes made by the write
+    ///
+    /// Unlike [`WriteValidator::convert_lines_to_buffer`], the rows are not organized into gen1
+    /// chunks, which is useful for sinks that write rows directly to the WAL or chunk them
+    /// differently. Tables are in the order they were first written to, and rows are in the
+    /// order they were written.
+    pub fn into_wal_rows(self) -> (IndexMap<TableId, Vec<Row>>, Option<OrderedCatalogBatch>) {
+        let mut table_rows: IndexMap<TableId, Vec<Row>> = IndexMap::new();
+        for line in self.lines {
+            table_rows.entry(line.table_id).or_default().push(line.row);
+        }
+        (table_rows, self.catalog_batch)
+    }
+
+    /// Convert this set of parsed and qualified lines into an Arrow [`RecordBatch`] per table
+    ///
+    /// The record batches use the schema of each table, including any columns added by this
+    /// write, with nulls for fields that a row does not have a value for. This does not
+    /// organize rows into gen1 chunks, and is useful for querying a write in memory.
+    pub fn to_record_batches(&self) -> Result<HashMap<TableId, RecordBatch>> {
+        let mut table_rows: IndexMap<TableId, Vec<&Row>> = IndexMap::new();
+        for line in &self.lines {
+            table_rows.entry(line.table_id).or_default().push(&line.row);
+        }
+        let mut batches = HashMap::with_capacity(table_rows.len());
+        for (table_id, rows) in table_rows {
+            let table_def = self
+                .validated_schema
+                .table_definition_by_id(&table_id)
+                .expect("validated lines are for tables in the validated schema");
+            batches.insert(table_id, record_batch_from_rows(table_def, rows)?);
+        }
+        Ok(batches)
     }
 }
 
@@ -63,16 +546,436 @@ impl WriteValidator<WithCatalog> {
         catalog: Arc<Catalog>,
         time_now_ns: i64,
     ) -> Result<WriteValidator<WithCatalog>> {
+        // get the sequence number first, so that any change to the catalog after it will cause
+        // the schema to be refreshed by a reused validator:
+        let catalog_sequence = catalog.sequence_number();
         let db_schema = catalog.db_or_create(db_name.as_str())?;
         Ok(WriteValidator {
             state: WithCatalog {
-                catalog,
+                catalog: Some(catalog),
+                catalog_sequence,
+                config: ValidatorConfig {
+                    default_tags: default_tags(&db_schema),
+                    ..Default::default()
+                },
                 db_schema,
                 time_now_ns,
             },
         })
     }
 
+    /// Initialize the [`WriteValidator`] with a read-only snapshot of a [`DatabaseSchema`],
+    /// e.g., on a read replica that validates writes before forwarding them
+    ///
+    /// The validator never updates the catalog. Instead, lines that would require a change
+    /// to the schema, i.e., that create a table or add columns to one, are rejected with a
+    /// [`WriteLineError`].
+    pub fn initialize_read_only(db_schema: Arc<DatabaseSchema>) -> WriteValidator<WithCatalog> {
+        WriteValidator {
+            state: WithCatalog {
+                catalog: None,
+                catalog_sequence: CatalogSequenceNumber::default(),
+                time_now_ns: 0,
+                config: ValidatorConfig {
+                    read_only: true,
+                    default_tags: default_tags(&db_schema),
+                    ..Default::default()
+                },
+                db_schema,
+            },
+        }
+    }
+
+    /// Initialize the [`WriteValidator`] with a frozen version of the [`DatabaseSchema`], e.g.,
+    /// as fetched from the catalog at the start of a long-running import job
+    ///
+    /// Lines are validated against the frozen schema, ignoring changes made to the catalog by
+    /// other writers since. Tables and columns can still be created, as changes to the frozen
+    /// schema, and are applied to the catalog. A write that creates a table or column that was
+    /// created by another writer since the schema was frozen fails with
+    /// [`Error::FrozenSchemaConflict`].
+    ///
+    /// When the validator is reused, each write is validated against the frozen schema along
+    /// with the changes made by the previous writes of the validator.
+    pub fn initialize_frozen(
+        db_schema: Arc<DatabaseSchema>,
+        catalog: Arc<Catalog>,
+        time_now_ns: i64,
+    ) -> WriteValidator<WithCatalog> {
+        WriteValidator {
+            state: WithCatalog {
+                catalog: Some(catalog),
+                catalog_sequence: CatalogSequenceNumber::default(),
+                time_now_ns,
+                config: ValidatorConfig {
+                    frozen_schema: true,
+                    default_tags: default_tags(&db_schema),
+                    ..Default::default()
+                },
+                db_schema,
+            },
+        }
+    }
+
+    /// Check that timestamps are no further than `max_skew` ahead of the ingest time
+    ///
+    /// Timestamps that are too far in the future typically come from clients with a
+    /// misconfigured clock. By default, such lines are accepted with a [`WriteLineWarning`]
+    /// that reports the amount of skew, but they can be rejected using [`ClockSkewPolicy::Reject`].
+    pub fn with_max_future_skew(mut self, max_skew: Duration, policy: ClockSkewPolicy) -> Self {
+        self.state.config.max_future_skew = Some((max_skew, policy));
+        self
+    }
+
+    /// Sum the size of the values written to each column, which is reported in the
+    /// `per_column_bytes` of the [`ValidatedLines`]
+    ///
+    /// This can be used to find the columns that contribute the most to the size of a write.
+    pub fn with_column_size_accounting(mut self) -> Self {
+        self.state.config.column_size_accounting = true;
+        self
+    }
+
+    /// Set how measurement names that are not safe for use in file paths are handled, see
+    /// [`MeasurementNamePolicy`]
+    pub fn with_measurement_name_policy(mut self, policy: MeasurementNamePolicy) -> Self {
+        self.state.config.measurement_name_policy = policy;
+        self
+    }
+
+    /// Set how the case of measurement names is normalized before they are resolved to a
+    /// table, see [`MeasurementCasePolicy`]
+    ///
+    /// Care is needed when enabling case folding on a database that already has tables whose
+    /// names are not in the folded form: writes to those tables are written to the table with
+    /// the folded name instead, which is created if it does not exist.
+    pub fn with_measurement_case_policy(mut self, policy: MeasurementCasePolicy) -> Self {
+        self.state.config.measurement_case_policy = policy;
+        self
+    }
+
+    /// Reject writes whose line protocol is larger than `limit` bytes with
+    /// [`Error::BatchTooLarge`], before any of it is parsed
+    pub fn with_max_batch_bytes(mut self, limit: usize) -> Self {
+        self.state.config.max_batch_bytes = Some(limit);
+        self
+    }
+
+    /// Limit the number of lines in a single write to `max_lines`, e.g., [`DEFAULT_MAX_LINES`]
+    ///
+    /// This bounds the work done for a write independently of its size in bytes, e.g., for a
+    /// write of many tiny lines. Lines are counted as lines of the input, including blank lines
+    /// and comments. Parsing stops at the first line after the maximum, and the write is then
+    /// handled according to the [`MaxLinesPolicy`].
+    pub fn with_max_lines(mut self, max_lines: usize, policy: MaxLinesPolicy) -> Self {
+        self.state.config.max_lines = Some((max_lines, policy));
+        self
+    }
+
+    /// Reject lines that would create a tag or field column whose name does not match the
+    /// given pattern with a [`WriteLineError`], e.g., to enforce a naming convention
+    ///
+    /// Only the names of new columns are checked, so lines can still be written to existing
+    /// columns that do not match. The pattern matches anywhere in a name, so it should be
+    /// anchored to check whole names, e.g., `^[a-z][a-z0-9_]*$` for snake case.
+    pub fn with_column_name_pattern(mut self, pattern: Regex) -> Self {
+        self.state.config.column_name_pattern = Some(pattern);
+        self
+    }
+
+    /// Split the measurement name of each line into the name of the table to write it to and
+    /// tags to add to it, e.g., for clients that write `cpu.region.us` for the `cpu` table
+    /// with a `region` tag of `us`
+    ///
+    /// The split name is checked against the [`MeasurementNamePolicy`] and
+    /// [`MeasurementCasePolicy`] in place of the measurement name. The tags are written as if
+    /// they were in the line, creating columns as needed, except that a tag of the same name
+    /// in the line takes precedence.
+    pub fn with_measurement_splitter(
+        mut self,
+        measurement_splitter: impl Fn(&str) -> (Arc<str>, Vec<(Arc<str>, Arc<str>)>)
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        self.state.config.measurement_splitter =
+            Some(MeasurementSplitter(Box::new(measurement_splitter)));
+        self
+    }
+
+    /// Record the precision applied to the timestamp of each valid line, in the
+    /// `effective_precisions` of the [`ValidatedLines`]
+    ///
+    /// This is for debugging writes with [`Precision::Auto`] or [`Precision::AutoBatch`],
+    /// where the precision is guessed from the timestamps.
+    pub fn with_effective_precisions(mut self) -> Self {
+        self.state.config.effective_precisions = true;
+        self
+    }
+
+    /// Produce the encoded series key of each valid line, which is reported in the
+    /// `series_keys` of the [`ValidatedLines`], see [`encode_series_key`] for the encoding
+    pub fn with_series_keys(mut self) -> Self {
+        self.state.config.series_keys = true;
+        self
+    }
+
+    /// Add the given tags to every line that is written, e.g., to record the node that
+    /// ingested the write
+    ///
+    /// Columns are created for the tags as needed. If a line sets one of the injected tags
+    /// itself, the injected value is used. Injected tags do not count towards the index count
+    /// of the write.
+    pub fn with_injected_tags(mut self, tags: Vec<(Arc<str>, Arc<str>)>) -> Self {
+        self.state.config.injected_tags = tags;
+        self
+    }
+
+    /// Reject writes that would produce a catalog batch with more than `limit` ops, i.e., table
+    /// creations or field additions, with [`Error::TooManyCatalogOps`], before the batch is
+    /// applied to the catalog
+    pub fn with_max_catalog_ops(mut self, limit: usize) -> Self {
+        self.state.config.max_catalog_ops = Some(limit);
+        self
+    }
+
+    /// Produce a hash of each valid row, which is reported in the `row_hashes` of the
+    /// [`ValidatedLines`], see [`row_hash`] for how it is computed
+    ///
+    /// These can be used to discard rows that were already written by a previous request, e.g.,
+    /// from clients that retry writes.
+    pub fn with_row_hashes(mut self) -> Self {
+        self.state.config.row_hashes = true;
+        self
+    }
+
+    /// Accept float values that are integral, e.g., `1e3` or `42.0`, for existing integer columns
+    /// by converting them to integers
+    ///
+    /// Float values that are out of range of an integer are rejected, as are values that are not
+    /// integral unless a [`FloatToIntPolicy`] that repairs them is set.
+    pub fn with_integral_floats_as_integers(mut self) -> Self {
+        self.state.config.integral_floats_as_integers = true;
+        self
+    }
+
+    /// Set how float values written to existing integer columns are handled
+    ///
+    /// With a policy other than [`FloatToIntPolicy::Error`], each value that is repaired to
+    /// an integer produces a [`WriteLineWarning`] with the original and repaired values. Values
+    /// that are out of range of an integer are always rejected.
+    pub fn with_float_to_int_policy(mut self, policy: FloatToIntPolicy) -> Self {
+        self.state.config.float_to_int_policy = policy;
+        self
+    }
+
+    /// Interpret line timestamps as deltas from the given base timestamp in nanoseconds
+    ///
+    /// Deltas are in the precision of the write, and are converted to nanoseconds before being
+    /// added to the base. Automatic precisions do not make sense for deltas, so they are
+    /// treated as nanoseconds. Lines with negative deltas, or deltas that overflow the
+    /// timestamp, are rejected.
+    pub fn with_timestamp_deltas(mut self, timestamp_base_ns: i64) -> Self {
+        self.state.config.timestamp_base_ns = Some(timestamp_base_ns);
+        self
+    }
+
+    /// Call the given function with the ID and name of each table created by the write
+    ///
+    /// This is called once per table as lines are validated, after the table has been added
+    /// to the validator's copy of the schema, and before the catalog is updated. It is not
+    /// called by [`check_lines`][WriteValidator::check_lines]. If the write
+    /// is later rejected, e.g., because it has an invalid line and partial writes are not
+    /// accepted, the table will not be created in the catalog.
+    pub fn with_on_table_created(
+        mut self,
+        on_table_created: impl FnMut(TableId, &str) + Send + 'static,
+    ) -> Self {
+        self.state.config.on_table_created =
+            Some(TableCreatedCallback(Mutex::new(Box::new(on_table_created))));
+        self
+    }
+
+    /// Check each row against custom rules, e.g., that a field is within a range of values
+    ///
+    /// The function is called with each row once it has been qualified, along with the
+    /// definition of its table, including any columns added by the line. A row for which it
+    /// returns an error is rejected with a [`WriteLineError`] containing the error message, and
+    /// makes no change to the schema.
+    pub fn with_row_validator(
+        mut self,
+        row_validator: impl Fn(&Row, &TableDefinition) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.state.config.row_validator = Some(RowValidator(Box::new(row_validator)));
+        self
+    }
+
+    /// Keep the original text of each valid line alongside its row, e.g., for an audit log of
+    /// accepted writes
+    ///
+    /// This roughly doubles the memory used by the validated lines.
+    pub fn with_original_lines(mut self) -> Self {
+        self.state.config.original_lines = true;
+        self
+    }
+
+    /// Set how lines that have no tags or fields left after any transforms are handled
+    pub fn with_empty_row_policy(mut self, policy: EmptyRowPolicy) -> Self {
+        self.state.config.empty_row_policy = policy;
+        self
+    }
+
+    /// Check that the span between the earliest and latest timestamps of the lines in a write
+    /// does not exceed `max_span`
+    ///
+    /// A write that spans a long time range is usually a client bug, e.g., a mix of precisions,
+    /// and would scatter its rows across many gen1 chunks.
+    pub fn with_max_timestamp_span(
+        mut self,
+        max_span: Duration,
+        policy: TimestampSpanPolicy,
+    ) -> Self {
+        self.state.config.max_timestamp_span = Some((max_span, policy));
+        self
+    }
+
+    /// Limit the number of distinct values accepted for the tags with the given names in a
+    /// single write
+    ///
+    /// Once a tag has as many distinct values as its budget, lines with a new value for it are
+    /// rejected. This is a circuit breaker for tags that are at risk of cardinality explosions,
+    /// e.g., a `user_id` tag.
+    pub fn with_tag_cardinality_budgets(mut self, budgets: HashMap<Arc<str>, usize>) -> Self {
+        self.state.config.tag_cardinality_budgets = budgets;
+        self
+    }
+
+    /// Verify that every column referenced by the validated rows exists in the catalog once
+    /// any schema changes made by the write have been applied to it
+    ///
+    /// This guards against the rows diverging from the catalog, e.g., if the schema the lines
+    /// were validated against was stale, or a catalog update was only partially applied. The
+    /// rows are verified before the write's schema changes are applied, so a write that fails
+    /// verification makes no change to the catalog.
+    pub fn with_column_id_verification(mut self) -> Self {
+        self.state.config.verify_column_ids = true;
+        self
+    }
+
+    /// Check that the copy of the schema that the validator updates as it validates lines
+    /// matches the catalog once the schema changes made by the write have been applied to it
+    ///
+    /// Every table and column in the validator's copy must be in the catalog with the same ID,
+    /// name and type, and the series key of each table must be a prefix of the catalog's. This
+    /// is a debugging aid for catching drift between the two: a mismatch panics in this crate's
+    /// tests, and is logged as an error otherwise, including in debug builds of the server. The
+    /// write itself is not affected. Only writes that change the schema are checked.
+    pub fn with_schema_consistency_check(mut self) -> Self {
+        self.state.config.check_schema_consistency = true;
+        self
+    }
+
+    /// Set how lines that are not compatible with the existing schema are handled
+    ///
+    /// With [`IncompatibleLineMode::DropIncompatible`], the schema is never changed by a write,
+    /// and lines that would change it, or have a value of the wrong type, are dropped rather
+    /// than failing the write, for clients that prefer lossy ingestion over rejection.
+    pub fn with_incompatible_line_mode(mut self, mode: IncompatibleLineMode) -> Self {
+        self.state.config.incompatible_line_mode = mode;
+        self
+    }
+
+    /// Reject lines whose timestamp in nanoseconds is at or below `min_timestamp_ns`
+    ///
+    /// The check is made on the final timestamp, after the precision is applied and lines
+    /// without a timestamp are given the ingest time, so e.g., a minimum of `0` rejects
+    /// uninitialized or sentinel timestamps at or before the epoch.
+    pub fn with_min_timestamp(mut self, min_timestamp_ns: i64) -> Self {
+        self.state.config.min_timestamp_ns = Some(min_timestamp_ns);
+        self
+    }
+
+    /// Skip creating columns for tags whose value is empty in every line of a write
+    ///
+    /// This avoids polluting the schema of v1 tables with tags that are always blank. The lines
+    /// are scanned before they are validated to find the tags that ever have a value, and a
+    /// [`WriteLineWarning`] is produced for each tag that is skipped. Tags that already have a
+    /// column are written as usual.
+    pub fn with_skip_empty_tags(mut self) -> Self {
+        self.state.config.skip_empty_tags = true;
+        self
+    }
+
+    /// Check that string field values are no larger than `max_bytes`
+    ///
+    /// This bounds the memory used by a single oversized value. By default, lines with a larger
+    /// value are rejected, but the value can be truncated using [`StringLengthPolicy::Truncate`].
+    pub fn with_max_string_field_bytes(
+        mut self,
+        max_bytes: usize,
+        policy: StringLengthPolicy,
+    ) -> Self {
+        self.state.config.max_string_field_bytes = Some((max_bytes, policy));
+        self
+    }
+
+    /// Check that the columns of each row resolve to the expected IDs, given by table and
+    /// column name, failing the write with [`Error::ColumnIdMismatch`] if any do not
+    ///
+    /// This is for testing the correctness of replaying writes, where the same line protocol
+    /// should always resolve to the same column IDs. Columns that are not in the mapping are not
+    /// checked. The check is made before the catalog is updated.
+    pub fn with_expected_column_ids(
+        mut self,
+        expected: HashMap<Arc<str>, HashMap<Arc<str>, ColumnId>>,
+    ) -> Self {
+        self.state.config.expected_column_ids = expected;
+        self
+    }
+
+    /// Set how lines for an existing table that have none of the tags in its series key are
+    /// handled, see [`SeriesKeyMissingPolicy`]
+    pub fn with_series_key_missing_policy(mut self, policy: SeriesKeyMissingPolicy) -> Self {
+        self.state.config.series_key_missing_policy = policy;
+        self
+    }
+
+    /// Validate the lines as written by a client that declares the series key of each table
+    /// explicitly, as in v3, rather than one that can write any tags to a table, as in v1
+    ///
+    /// Tables created by the write have the tags of the line that creates them as their
+    /// declared series key. A line for an existing table whose series key is made up of the
+    /// tags written to it is rejected with a [`WriteLineError`]. Whether or not this is set, a
+    /// line with a tag that is not in the declared series key of its table is rejected, rather
+    /// than adding the tag to the key.
+    pub fn with_explicit_series_keys(mut self) -> Self {
+        self.state.config.explicit_series_keys = true;
+        self
+    }
+
+    /// Time the phases of validating each write, i.e., parsing the lines, applying the changes
+    /// to the catalog, and organizing the rows into chunks, which are reported in the
+    /// `phase_durations` of the [`ValidatedLines`]
+    ///
+    /// This is useful for finding where the time goes on slow writes. When this is not enabled,
+    /// the phases are not timed.
+    pub fn with_phase_durations(mut self) -> Self {
+        self.state.config.phase_durations = true;
+        self
+    }
+
+    /// Create new columns with the given types, by column name, rather than inferring their
+    /// type from the first value written to them
+    ///
+    /// For example, a field hinted as a float is created as a float column even if the first
+    /// value written to it is an integer. Integer values written to a hinted float column are
+    /// converted to floats. A line that writes to an existing column whose type differs from
+    /// the hint for it is rejected with a [`WriteLineError`].
+    pub fn with_column_type_hints(mut self, hints: HashMap<Arc<str>, InfluxColumnType>) -> Self {
+        self.state.config.column_type_hints = hints;
+        self
+    }
+
     /// Parse the incoming lines of line protocol and update the
     /// [`DatabaseSchema`] if:
     ///
@@ -83,35 +986,172 @@ impl WriteValidator<WithCatalog> {
     ///
     /// If this function succeeds, then the catalog will receive an update, so
     /// steps following this should be infallible.
+    ///
+    /// If updating the catalog fails, the error is returned, and the catalog is left unchanged.
+    /// The changes that the write made to its own copy of the schema are discarded along with
+    /// the lines, so no partial schema state leaks out of a failed write.
     pub fn parse_lines_and_update_schema(
         self,
         lp: &str,
         accept_partial: bool,
-        ingest_time: Time,
+        ingest_time: impl TimeSource,
+        precision: Precision,
+    ) -> Result<WriteValidator<LinesParsed>> {
+        self.validate_lines(
+            lp,
+            accept_partial,
+            ingest_time.now_ns(),
+            precision,
+            &mut Scratch::default(),
+        )
+    }
+
+    /// Parse the incoming lines of line protocol and update the [`DatabaseSchema`], as with
+    /// [`parse_lines_and_update_schema`], without consuming the validator
+    ///
+    /// This allows a validator to be initialized once, and reused for many writes to the same
+    /// database, e.g., on a long-lived connection. The handle to the [`DatabaseSchema`] is only
+    /// fetched again if the catalog has changed since it was last fetched, and the ingest time
+    /// is used as the time of any catalog updates.
+    ///
+    /// For a validator with a [frozen schema][WriteValidator::initialize_frozen], the schema is
+    /// never fetched again, but includes the changes made by each write.
+    ///
+    /// The validator's handle to the [`DatabaseSchema`] is only ever replaced by a schema from
+    /// the catalog, or, for a frozen schema, by the schema of a successful write. So if a write
+    /// fails, including when updating the catalog fails, the validator is left as it was before
+    /// any changes made by the write, and is safe to reuse.
+    ///
+    /// [`parse_lines_and_update_schema`]: WriteValidator::parse_lines_and_update_schema
+    pub fn parse_lines_and_update_schema_reusing(
+        &mut self,
+        lp: &str,
+        accept_partial: bool,
+        ingest_time: impl TimeSource,
+        precision: Precision,
+    ) -> Result<WriteValidator<LinesParsed>> {
+        self.parse_lines_with_scratch(
+            &mut Scratch::default(),
+            lp,
+            accept_partial,
+            ingest_time,
+            precision,
+        )
+    }
+
+    /// Parse the incoming lines of line protocol and update the [`DatabaseSchema`], as with
+    /// [`parse_lines_and_update_schema_reusing`], reusing the buffers in the given [`Scratch`]
+    ///
+    /// This is for servers that validate many writes, which can keep a [`Scratch`] per
+    /// connection or worker, to avoid allocating the same buffers for every line of every
+    /// write. The result is identical to validating without the scratch buffers. A write that
+    /// fails may leave the buffers without their allocations, which are then made again.
+    ///
+    /// [`parse_lines_and_update_schema_reusing`]: WriteValidator::parse_lines_and_update_schema_reusing
+    pub fn parse_lines_with_scratch(
+        &mut self,
+        scratch: &mut Scratch,
+        lp: &str,
+        accept_partial: bool,
+        ingest_time: impl TimeSource,
+        precision: Precision,
+    ) -> Result<WriteValidator<LinesParsed>> {
+        let ingest_time_ns = ingest_time.now_ns();
+        if !self.state.config.frozen_schema {
+            self.refresh_db_schema();
+        }
+        self.state.time_now_ns = ingest_time_ns;
+        let lines_parsed =
+            self.validate_lines(lp, accept_partial, ingest_time_ns, precision, scratch)?;
+        if self.state.config.frozen_schema {
+            self.state.db_schema = Arc::clone(&lines_parsed.state.validated_schema);
+        }
+        Ok(lines_parsed)
+    }
+
+    /// Fetch the handle to the [`DatabaseSchema`] again if the catalog has changed since it was
+    /// last fetched
+    fn refresh_db_schema(&mut self) {
+        let Some(catalog) = &self.state.catalog else {
+            return;
+        };
+        let catalog_sequence = catalog.sequence_number();
+        if catalog_sequence == self.state.catalog_sequence {
+            return;
+        }
+        if let Some(db_schema) = catalog.db_schema_by_id(&self.state.db_schema.id) {
+            self.state.config.default_tags = default_tags(&db_schema);
+            self.state.db_schema = db_schema;
+        }
+        self.state.catalog_sequence = catalog_sequence;
+    }
+
+    fn validate_lines(
+        &self,
+        lp: &str,
+        accept_partial: bool,
+        ingest_time_ns: i64,
         precision: Precision,
+        scratch: &mut Scratch,
     ) -> Result<WriteValidator<LinesParsed>> {
+        self.check_batch_size(lp)?;
+
+        let start = self.state.config.phase_durations.then(Instant::now);
         let mut errors = vec![];
-        let mut lp_lines = lp.lines();
+        let mut parse_error_count = 0;
         let mut lines = vec![];
         let mut bytes = 0;
         let mut catalog_updates = vec![];
+        let mut batch_state = BatchState {
+            scratch: std::mem::take(scratch),
+            ..Default::default()
+        };
         let mut schema = Cow::Borrowed(self.state.db_schema.as_ref());
+        let precision = resolve_batch_precision(lp, precision);
+        if self.state.config.skip_empty_tags {
+            batch_state.non_empty_tags = Some(non_empty_tags(lp, &self.state.config));
+        }
 
-        for (line_idx, maybe_line) in parse_lines(lp).enumerate() {
+        let mut lines_truncated = false;
+        for (line_idx, raw_line) in split_lines(lp) {
+            if self.line_limit_reached(line_idx)? {
+                lines_truncated = true;
+                break;
+            }
+            let Some(maybe_line) = parse_lines(raw_line).next() else {
+                continue;
+            };
             let (qualified_line, catalog_op) = match maybe_line
-                .map_err(|e| WriteLineError {
-                    // This unwrap is fine because we're moving line by line
-                    // alongside the output from parse_lines
-                    original_line: lp_lines.next().unwrap().to_string(),
-                    line_number: line_idx + 1,
-                    error_message: e.to_string(),
+                .map_err(|e| {
+                    parse_error_count += 1;
+                    WriteLineError {
+                        original_line: raw_line.to_string(),
+                        line_number: line_idx + 1,
+                        error_message: e.to_string(),
+                    }
                 })
                 .and_then(|l| {
-                    let raw_line = lp_lines.next().unwrap();
-                    validate_and_qualify_line(&mut schema, line_idx, l, ingest_time, precision)
-                        .inspect(|_| bytes += raw_line.len() as u64)
+                    validate_and_qualify_line(
+                        &mut schema,
+                        line_idx,
+                        l,
+                        ingest_time_ns,
+                        precision,
+                        &self.state.config,
+                        &mut batch_state,
+                    )
+                    .map(|validated| {
+                        let (mut qualified_line, catalog_op) = validated?;
+                        bytes += raw_line.len() as u64;
+                        if self.state.config.original_lines {
+                            qualified_line.original_line = Some(raw_line.to_string());
+                        }
+                        Some((qualified_line, catalog_op))
+                    })
                 }) {
-                Ok((qualified_line, catalog_op)) => (qualified_line, catalog_op),
+                Ok(Some((qualified_line, catalog_op))) => (qualified_line, catalog_op),
+                // the line was skipped:
+                Ok(None) => continue,
                 Err(e) => {
                     if !accept_partial {
                         return Err(Error::ParseError(e));
@@ -124,100 +1164,571 @@ impl WriteValidator<WithCatalog> {
             if let Some(op) = catalog_op {
                 catalog_updates.push(op);
             }
-            // This unwrap is fine because we're moving line by line
-            // alongside the output from parse_lines
             lines.push(qualified_line);
         }
+        *scratch = std::mem::take(&mut batch_state.scratch);
+
+        let parse_duration = start.map(|start| start.elapsed());
 
         // All lines are parsed and validated, so all steps after this
         // are infallible, therefore, update the catalog if changes were
-        // made to the schema:
-        let catalog_batch = if catalog_updates.is_empty() {
-            None
-        } else {
-            let catalog_batch = CatalogBatch {
-                database_id: self.state.db_schema.id,
-                time_ns: self.state.time_now_ns,
-                database_name: Arc::clone(&self.state.db_schema.name),
-                ops: catalog_updates,
-            };
-            self.state.catalog.apply_catalog_batch(&catalog_batch)?
+        // made to the schema. The changes are only made to the local copy of
+        // the schema until then, so if applying them to the catalog fails, it
+        // is dropped, and the validator state is untouched:
+        if let Some(limit) = self.state.config.max_catalog_ops {
+            if catalog_updates.len() > limit {
+                return Err(Error::TooManyCatalogOps {
+                    count: catalog_updates.len(),
+                    limit,
+                });
+            }
+        }
+        if !self.state.config.expected_column_ids.is_empty() {
+            verify_expected_column_ids(&schema, &self.state.config.expected_column_ids, &lines)?;
+        }
+        let catalog_batch = (!catalog_updates.is_empty()).then(|| CatalogBatch {
+            database_id: self.state.db_schema.id,
+            time_ns: self.state.time_now_ns,
+            database_name: Arc::clone(&self.state.db_schema.name),
+            ops: catalog_updates,
+        });
+        if let (true, Some(catalog)) = (self.state.config.verify_column_ids, &self.state.catalog) {
+            self.verify_pending_column_ids(catalog, catalog_batch.as_ref(), &lines)?;
+        }
+        let catalog_batch = match catalog_batch {
+            Some(catalog_batch) => {
+                let catalog = self
+                    .state
+                    .catalog
+                    .as_ref()
+                    .expect("read-only validators reject lines that change the schema");
+                if self.state.config.frozen_schema {
+                    if let Some(catalog_schema) = catalog.db_schema_by_id(&self.state.db_schema.id)
+                    {
+                        check_frozen_schema_conflicts(&catalog_schema, &catalog_batch.ops)?;
+                    }
+                }
+                catalog.apply_catalog_batch(&catalog_batch)?
+            }
+            None => None,
+        };
+        if let (true, Cow::Owned(schema), Some(catalog)) = (
+            self.state.config.check_schema_consistency,
+            &schema,
+            &self.state.catalog,
+        ) {
+            if let Some(catalog_schema) = catalog.db_schema_by_id(&self.state.db_schema.id) {
+                check_schema_consistency(schema, &catalog_schema);
+            }
+        }
+        let phase_durations = start
+            .zip(parse_duration)
+            .map(|(start, parse)| PhaseDurations {
+                parse,
+                catalog_apply: start.elapsed() - parse,
+                chunking: Duration::ZERO,
+            });
+        let validated_schema = match schema {
+            Cow::Borrowed(_) => Arc::clone(&self.state.db_schema),
+            Cow::Owned(schema) => Arc::new(schema),
         };
 
         Ok(WriteValidator {
             state: LinesParsed {
-                catalog: self.state,
+                before_schema: Arc::clone(&self.state.db_schema),
+                validated_schema,
                 lines,
                 errors,
+                parse_error_count,
+                warnings: batch_state.warnings,
+                dropped_incompatible_count: batch_state.dropped_incompatible_count,
+                per_column_bytes: batch_state.per_column_bytes,
                 bytes,
                 catalog_batch,
+                phase_durations,
+                lines_truncated,
             },
         })
     }
 }
 
+impl WriteValidator<WithCatalog> {
+    /// Check the incoming lines of line protocol against the [`DatabaseSchema`] without
+    /// producing rows or updating the catalog
+    ///
+    /// This runs the same parsing and validation as [`parse_lines_and_update_schema`], with
+    /// any configured policies, but discards the validated lines and the changes they make to
+    /// the validator's copy of the schema, so is a way to check whether a write would be
+    /// accepted without changing the catalog. All invalid lines are reported in the errors.
+    ///
+    /// [`parse_lines_and_update_schema`]: WriteValidator::parse_lines_and_update_schema
+    pub fn check_lines(
+        &self,
+        lp: &str,
+        ingest_time: impl TimeSource,
+        precision: Precision,
+    ) -> Result<CheckedLines> {
+        self.check_batch_size(lp)?;
+
+        let ingest_time_ns = ingest_time.now_ns();
+        let mut checked = CheckedLines::default();
+        let mut batch_state = BatchState {
+            check_only: true,
+            ..Default::default()
+        };
+        let mut schema = Cow::Borrowed(self.state.db_schema.as_ref());
+        let precision = resolve_batch_precision(lp, precision);
+        if self.state.config.skip_empty_tags {
+            batch_state.non_empty_tags = Some(non_empty_tags(lp, &self.state.config));
+        }
+
+        for (line_idx, raw_line) in split_lines(lp) {
+            if self.line_limit_reached(line_idx)? {
+                break;
+            }
+            let Some(maybe_line) = parse_lines(raw_line).next() else {
+                continue;
+            };
+            let result = maybe_line
+                .map_err(|e| WriteLineError {
+                    original_line: raw_line.to_string(),
+                    line_number: line_idx + 1,
+                    error_message: e.to_string(),
+                })
+                .and_then(|l| {
+                    validate_and_qualify_line(
+                        &mut schema,
+                        line_idx,
+                        l,
+                        ingest_time_ns,
+                        precision,
+                        &self.state.config,
+                        &mut batch_state,
+                    )
+                });
+            match result {
+                Ok(Some((qualified_line, _))) => {
+                    checked.line_count += 1;
+                    checked.index_count += qualified_line.index_count;
+                    checked.field_count += qualified_line.field_count;
+                }
+                Ok(None) => (),
+                Err(e) => checked.errors.push(e),
+            }
+        }
+        checked.warnings = batch_state.warnings;
+        checked.dropped_incompatible_count = batch_state.dropped_incompatible_count;
+
+        Ok(checked)
+    }
+
+    /// Verify the column ids of the lines against the schema that the catalog will have once
+    /// the write's catalog batch, if any, is applied to it, without applying it, so that a
+    /// write that fails verification leaves the catalog unchanged
+    fn verify_pending_column_ids(
+        &self,
+        catalog: &Catalog,
+        catalog_batch: Option<&CatalogBatch>,
+        lines: &[QualifiedLine],
+    ) -> Result<()> {
+        let catalog_schema = catalog
+            .db_schema_by_id(&self.state.db_schema.id)
+            .ok_or_else(|| Error::DatabaseNotFound {
+                db_name: self.state.db_schema.name.to_string(),
+            })?;
+        let pending_schema = match catalog_batch {
+            Some(catalog_batch) => {
+                DatabaseSchema::new_if_updated_from_batch(&catalog_schema, catalog_batch)?
+            }
+            None => None,
+        };
+        verify_column_ids(pending_schema.as_ref().unwrap_or(&catalog_schema), lines)
+    }
+
+    fn check_batch_size(&self, lp: &str) -> Result<()> {
+        if let Some(limit) = self.state.config.max_batch_bytes {
+            if lp.len() > limit {
+                return Err(Error::BatchTooLarge {
+                    size: lp.len(),
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether the line at the zero-based `line_idx` of the input is past the maximum
+    /// number of lines, in which case no more lines are validated, returning an error if the
+    /// write is rejected
+    fn line_limit_reached(&self, line_idx: usize) -> Result<bool> {
+        match self.state.config.max_lines {
+            Some((limit, policy)) if line_idx >= limit => match policy {
+                MaxLinesPolicy::Reject => Err(Error::TooManyLines { limit }),
+                MaxLinesPolicy::Truncate => Ok(true),
+            },
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Validate line protocol for several databases, returning the validated lines for each entry,
+/// by database
+///
+/// Each entry is validated, and has its catalog updates applied, independently and in order,
+/// so an error for one entry does not fail the others, including other entries for the same
+/// database. The results for each database are in the order of its entries, and the line
+/// numbers in any errors are those of the entry's own line protocol.
+pub fn validate_multi_db(
+    catalog: &Arc<Catalog>,
+    entries: Vec<(NamespaceName<'static>, &str)>,
+    accept_partial: bool,
+    ingest_time: impl TimeSource,
+    precision: Precision,
+    gen1_duration: Gen1Duration,
+) -> HashMap<NamespaceName<'static>, Vec<Result<ValidatedLines>>> {
+    let ingest_time_ns = ingest_time.now_ns();
+    let mut results: HashMap<NamespaceName<'static>, Vec<Result<ValidatedLines>>> = HashMap::new();
+    for (db_name, lp) in entries {
+        let result =
+            WriteValidator::initialize(db_name.clone(), Arc::clone(catalog), ingest_time_ns)
+                .and_then(|validator| {
+                    validator.parse_lines_and_update_schema(
+                        lp,
+                        accept_partial,
+                        ingest_time_ns,
+                        precision,
+                    )
+                })
+                .map(|lines_parsed| lines_parsed.convert_lines_to_buffer(gen1_duration));
+        results.entry(db_name).or_default().push(result);
+    }
+    results
+}
+
+/// Check that the tables and columns created by a write to a frozen schema were not also
+/// created in the catalog, with different IDs, since the schema was frozen
+fn check_frozen_schema_conflicts(catalog_schema: &DatabaseSchema, ops: &[CatalogOp]) -> Result<()> {
+    for op in ops {
+        let (table_id, table_name, field_definitions) = match op {
+            CatalogOp::CreateTable(table) => {
+                (table.table_id, &table.table_name, &table.field_definitions)
+            }
+            CatalogOp::AddFields(additions) => (
+                additions.table_id,
+                &additions.table_name,
+                &additions.field_definitions,
+            ),
+            _ => continue,
+        };
+        let Some(table_def) = catalog_schema.table_definition(Arc::clone(table_name)) else {
+            continue;
+        };
+        let conflict = table_def.table_id != table_id
+            || field_definitions.iter().any(|field| {
+                table_def
+                    .column_name_to_id(Arc::clone(&field.name))
+                    .is_some_and(|id| id != field.id)
+            });
+        if conflict {
+            return Err(Error::FrozenSchemaConflict {
+                table_name: table_name.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check that the columns of the rows of the lines resolve to the expected IDs, by table and
+/// column name, in the given schema
+fn verify_expected_column_ids(
+    db_schema: &DatabaseSchema,
+    expected: &HashMap<Arc<str>, HashMap<Arc<str>, ColumnId>>,
+    lines: &[QualifiedLine],
+) -> Result<()> {
+    for line in lines {
+        let Some(table_def) = db_schema.table_definition_by_id(&line.table_id) else {
+            continue;
+        };
+        let Some(expected_columns) = expected.get(&table_def.table_name) else {
+            continue;
+        };
+        for field in &line.row.fields {
+            let Some(column_name) = table_def.column_id_to_name(&field.id) else {
+                continue;
+            };
+            match expected_columns.get(&column_name) {
+                Some(expected_id) if *expected_id != field.id => {
+                    return Err(Error::ColumnIdMismatch {
+                        table_name: table_def.table_name.to_string(),
+                        column_name: column_name.to_string(),
+                        expected: *expected_id,
+                        actual: field.id,
+                    });
+                }
+                _ => (),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that every column referenced by the rows of the lines exists in the given schema
+fn verify_column_ids(db_schema: &DatabaseSchema, lines: &[QualifiedLine]) -> Result<()> {
+    for line in lines {
+        let table_def = db_schema.table_definition_by_id(&line.table_id);
+        for field in &line.row.fields {
+            if table_def
+                .as_ref()
+                .and_then(|t| t.column_definition_by_id(&field.id))
+                .is_none()
+            {
+                return Err(Error::ColumnNotInCatalog {
+                    table_id: line.table_id,
+                    column_id: field.id,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Report any mismatches between the validator's copy of the schema and the catalog's, see
+/// [`WriteValidator::with_schema_consistency_check`]
+fn check_schema_consistency(db_schema: &DatabaseSchema, catalog_schema: &DatabaseSchema) {
+    let mismatches = schema_mismatches(db_schema, catalog_schema);
+    if mismatches.is_empty() {
+        return;
+    }
+    if cfg!(test) {
+        panic!(
+            "schema of database '{db_name}' does not match the catalog: {mismatches}",
+            db_name = db_schema.name,
+            mismatches = mismatches.join("; "),
+        );
+    }
+    for mismatch in mismatches {
+        error!(db_name = %db_schema.name, %mismatch, "schema does not match the catalog");
+    }
+}
+
+/// Describe each table and column in the given schema that is not in the catalog's schema as
+/// it is in the given schema
+fn schema_mismatches(db_schema: &DatabaseSchema, catalog_schema: &DatabaseSchema) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    for table_def in db_schema.tables() {
+        let table_name = &table_def.table_name;
+        let Some(catalog_table) = catalog_schema.table_definition_by_id(&table_def.table_id) else {
+            mismatches.push(format!(
+                "table '{table_name}' with id {table_id} is not in the catalog",
+                table_id = table_def.table_id,
+            ));
+            continue;
+        };
+        if catalog_table.table_name != *table_name {
+            mismatches.push(format!(
+                "table '{table_name}' is named '{catalog_name}' in the catalog",
+                catalog_name = catalog_table.table_name,
+            ));
+        }
+        for col_def in table_def.columns.values() {
+            match catalog_table.column_definition_by_id(&col_def.id) {
+                None => mismatches.push(format!(
+                    "column '{col_name}' with id {col_id} of table '{table_name}' is not in \
+                    the catalog",
+                    col_name = col_def.name,
+                    col_id = col_def.id,
+                )),
+                Some(catalog_col) if catalog_col.name != col_def.name => mismatches.push(format!(
+                    "column '{col_name}' of table '{table_name}' is named '{catalog_name}' \
+                        in the catalog",
+                    col_name = col_def.name,
+                    catalog_name = catalog_col.name,
+                )),
+                Some(catalog_col) if catalog_col.data_type != col_def.data_type => {
+                    mismatches.push(format!(
+                        "column '{col_name}' of table '{table_name}' has type {col_type}, but \
+                        {catalog_type} in the catalog",
+                        col_name = col_def.name,
+                        col_type = col_def.data_type,
+                        catalog_type = catalog_col.data_type,
+                    ))
+                }
+                Some(_) => (),
+            }
+        }
+        if !catalog_table.series_key.starts_with(&table_def.series_key) {
+            mismatches.push(format!(
+                "series key of table '{table_name}' is {series_key:?}, but {catalog_key:?} in \
+                the catalog",
+                series_key = table_def.series_key_names,
+                catalog_key = catalog_table.series_key_names,
+            ));
+        }
+    }
+    mismatches
+}
+
 /// Type alias for storing new columns added by a write
 type ColumnTracker = Vec<(ColumnId, Arc<str>, InfluxColumnType)>;
 
-/// Validate a line of line protocol against the given schema definition
+/// Validate a line of line protocol against the given schema definition, returning `None` if
+/// the line is skipped
 ///
 /// This is for scenarios where a write comes in for a table that exists, but may have
-/// invalid field types, based on the pre-existing schema.
+/// invalid field types, based on the pre-existing schema. It is used both to write lines and
+/// to only check them, with [`WriteValidator::check_lines`], so both apply the same checks.
 fn validate_and_qualify_line(
     db_schema: &mut Cow<'_, DatabaseSchema>,
     line_number: usize,
     line: ParsedLine<'_>,
-    ingest_time: Time,
+    ingest_time_ns: i64,
+    precision: Precision,
+    config: &ValidatorConfig,
+    batch_state: &mut BatchState,
+) -> Result<Option<(QualifiedLine, Option<CatalogOp>)>, WriteLineError> {
+    batch_state.line.clear();
+    let split = split_measurement(&line, config);
+    let (measurement, split_tags) = split_measurement_parts(&line, &split);
+    let table_name =
+        qualify_measurement_name(&line, measurement, line_number, config, batch_state)?;
+    let mut tags = reuse_vec(std::mem::take(&mut batch_state.scratch.tags));
+    line_tags(&mut tags, &line, split_tags, config);
+    let mut new_tag_values = reuse_vec(std::mem::take(&mut batch_state.scratch.new_tag_values));
+    let qualified = qualify_line(
+        db_schema,
+        line_number,
+        &line,
+        table_name.as_ref(),
+        &tags,
+        &mut new_tag_values,
+        ingest_time_ns,
+        precision,
+        config,
+        batch_state,
+    );
+    if let Ok(Some(_)) = qualified {
+        record_tag_values(&mut new_tag_values, batch_state);
+        batch_state.accept_line();
+    }
+    batch_state.scratch.tags = reuse_vec(tags);
+    batch_state.scratch.new_tag_values = reuse_vec(new_tag_values);
+    qualified
+}
+
+/// Validate a line of line protocol with the given tags, as for [`validate_and_qualify_line`],
+/// collecting the new values of tags with a cardinality budget, which are only recorded if
+/// the line is accepted
+#[allow(clippy::too_many_arguments)]
+fn qualify_line<'a>(
+    db_schema: &mut Cow<'_, DatabaseSchema>,
+    line_number: usize,
+    line: &ParsedLine<'_>,
+    table_name: &str,
+    tags: &[LineTag<'a>],
+    new_tag_values: &mut Vec<(ColumnId, &'a str)>,
+    ingest_time_ns: i64,
     precision: Precision,
-) -> Result<(QualifiedLine, Option<CatalogOp>), WriteLineError> {
+    config: &ValidatorConfig,
+    batch_state: &mut BatchState,
+) -> Result<Option<(QualifiedLine, Option<CatalogOp>)>, WriteLineError> {
     let mut catalog_op = None;
-    let table_name = line.series.measurement.as_str();
-    let mut fields = Vec::with_capacity(line.column_count());
+    check_time_column_name(line, line_number, tags)?;
+    check_duplicate_columns(line, line_number, tags)?;
+    for tag in tags {
+        check_column_type_hint(line, line_number, tag.key, InfluxColumnType::Tag, config)?;
+    }
+    let mut fields = Vec::with_capacity(
+        line.column_count() + config.injected_tags.len() + config.default_tags.len(),
+    );
     let mut index_count = 0;
     let mut field_count = 0;
-    let qualified = if let Some(table_def) = db_schema.table_definition(table_name) {
-        // This table already exists, so update with any new columns if present:
-        let mut columns = ColumnTracker::with_capacity(line.column_count() + 1);
-        if let Some(tag_set) = &line.series.tag_set {
-            for (tag_key, tag_val) in tag_set {
-                if let Some(col_id) = table_def.column_name_to_id(tag_key.as_str()) {
-                    fields.push(Field::new(col_id, FieldData::Tag(tag_val.to_string())));
-                } else {
+    // lines without a timestamp are given the ingest time:
+    let timestamp_was_defaulted = line.timestamp.is_none();
+    let mut qualified = if let Some(table_def) = db_schema.table_definition(table_name) {
+        // This table already exists, so update with any new columns if present, which most
+        // lines do not have, so this only allocates once a column is added:
+        let mut columns = ColumnTracker::new();
+        check_series_key_semantics(line, line_number, &table_def, config)?;
+        for tag in tags {
+            let col_id = match table_def.column_name_to_id(tag.key) {
+                Some(col_id) => col_id,
+                None if skip_empty_tag(line, line_number, table_name, tag, batch_state) => {
+                    continue;
+                }
+                None if table_def.explicit_series_key => {
+                    return Err(tag_not_in_series_key(line, line_number, &table_def, tag));
+                }
+                None => {
+                    check_column_name(line, line_number, tag.key, config)?;
                     let col_id = ColumnId::new();
-                    fields.push(Field::new(col_id, FieldData::Tag(tag_val.to_string())));
-                    columns.push((col_id, tag_key.as_str().into(), InfluxColumnType::Tag));
+                    columns.push((col_id, tag.key.into(), InfluxColumnType::Tag));
+                    col_id
                 }
+            };
+            if check_tag_cardinality(line, line_number, col_id, tag, config, batch_state)? {
+                new_tag_values.push((col_id, tag.value));
+            }
+            fields.push(Field::new(col_id, FieldData::Tag(tag.value.to_string())));
+            if tag.from_line {
                 index_count += 1;
             }
         }
+        if series_key_missing(line, line_number, &table_def, tags, config)? {
+            for col_id in table_def.series_key_ids() {
+                fields.push(Field::new(*col_id, FieldData::Tag(String::new())));
+            }
+        }
         for (field_name, field_val) in line.field_set.iter() {
             // This field already exists, so check the incoming type matches existing type:
             if let Some((col_id, col_def)) = table_def.column_id_and_definition(field_name.as_str())
             {
                 let field_col_type = influx_column_type_from_field_value(field_val);
                 let existing_col_type = col_def.data_type;
+                check_column_type_hint(
+                    line,
+                    line_number,
+                    field_name.as_str(),
+                    existing_col_type,
+                    config,
+                )?;
                 if field_col_type != existing_col_type {
-                    let field_name = field_name.to_string();
-                    return Err(WriteLineError {
-                        original_line: line.to_string(),
-                        line_number: line_number + 1,
-                        error_message: format!(
-                            "invalid field value in line protocol for field '{field_name}' on line \
-                            {line_number}: expected type {expected}, but got {got}",
-                            expected = existing_col_type,
-                            got = field_col_type,
-                        ),
-                    });
+                    let value = match coerce_field_value(
+                        line,
+                        line_number,
+                        field_name.as_str(),
+                        field_val,
+                        existing_col_type,
+                        config,
+                        batch_state,
+                    ) {
+                        Ok(value) => value,
+                        Err(_) if drop_incompatible(config, batch_state) => return Ok(None),
+                        Err(e) => return Err(e),
+                    };
+                    fields.push(Field::new(col_id, value));
+                } else {
+                    let value = field_data(
+                        line,
+                        line_number,
+                        field_name.as_str(),
+                        field_val,
+                        config,
+                        batch_state,
+                    )?;
+                    fields.push(Field::new(col_id, value));
                 }
-                fields.push(Field::new(col_id, field_val));
             } else {
                 let col_id = ColumnId::new();
-                columns.push((
-                    col_id,
-                    Arc::from(field_name.as_str()),
-                    influx_column_type_from_field_value(field_val),
-                ));
-                fields.push(Field::new(col_id, field_val));
+                let (col_type, value) = new_column_value(
+                    line,
+                    line_number,
+                    field_name.as_str(),
+                    field_val,
+                    config,
+                    batch_state,
+                )?;
+                columns.push((col_id, Arc::from(field_name.as_str()), col_type));
+                fields.push(Field::new(col_id, value));
             }
             field_count += 1;
         }
@@ -233,18 +1744,39 @@ fn validate_and_qualify_line(
                 ));
                 col_id
             });
-        let timestamp_ns = line
-            .timestamp
-            .map(|ts| apply_precision_to_timestamp(precision, ts))
-            .unwrap_or(ingest_time.timestamp_nanos());
+        let (timestamp_ns, applied_precision) = qualify_timestamp(
+            line,
+            line_number,
+            ingest_time_ns,
+            precision,
+            config,
+            batch_state,
+        )?;
 
         fields.push(Field::new(time_col_id, FieldData::Timestamp(timestamp_ns)));
+        if !keep_row(line, line_number, fields.len() > 1, config, batch_state)? {
+            return Ok(None);
+        }
+        let row = Row {
+            time: timestamp_ns,
+            fields,
+        };
 
         // if we have new columns defined, add them to the db_schema table so that subsequent lines
         // won't try to add the same definitions. Collect these additions into a catalog op, which
         // will be applied to the catalog with any other ops after all lines in the write request
         // have been parsed and validated.
         if !columns.is_empty() {
+            if drop_incompatible(config, batch_state) {
+                return Ok(None);
+            }
+            if config.read_only {
+                return Err(catalog_change_error(
+                    line,
+                    line_number,
+                    &format!("add columns to table '{table_name}'"),
+                ));
+            }
             let database_name = Arc::clone(&db_schema.name);
             let database_id = db_schema.id;
             let table_name: Arc<str> = Arc::clone(&table_def.table_name);
@@ -255,14 +1787,7 @@ fn validate_and_qualify_line(
                 field_definitions.push(FieldDefinition::new(*id, Arc::clone(name), influx_type));
             }
 
-            let db_schema = db_schema.to_mut();
-            let mut new_table_def = db_schema
-                .tables
-                .get_mut(&table_id)
-                // unwrap is safe due to the surrounding if let condition:
-                .unwrap()
-                .as_ref()
-                .clone();
+            let mut new_table_def = table_def.as_ref().clone();
             new_table_def
                 .add_columns(columns)
                 .map_err(|e| WriteLineError {
@@ -270,7 +1795,9 @@ fn validate_and_qualify_line(
                     line_number: line_number + 1,
                     error_message: e.to_string(),
                 })?;
+            validate_row(line, line_number, &row, &new_table_def, config)?;
             db_schema
+                .to_mut()
                 .insert_table(table_id, Arc::new(new_table_def))
                 .map_err(|e| WriteLineError {
                     original_line: line.to_string(),
@@ -285,39 +1812,67 @@ fn validate_and_qualify_line(
                 table_name,
                 field_definitions,
             }));
+        } else {
+            validate_row(line, line_number, &row, &table_def, config)?;
         }
         QualifiedLine {
             table_id: table_def.table_id,
-            row: Row {
-                time: timestamp_ns,
-                fields,
-            },
+            row,
             index_count,
             field_count,
+            series_key_bytes: None,
+            row_hash: None,
+            timestamp_was_defaulted,
+            table_created: false,
+            original_line: None,
+            effective_precision: applied_precision
+                .filter(|_| config.effective_precisions)
+                .map(|precision| (line_number + 1, precision)),
         }
     } else {
+        if drop_incompatible(config, batch_state) {
+            return Ok(None);
+        }
+        if config.read_only {
+            return Err(catalog_change_error(
+                line,
+                line_number,
+                &format!("create table '{table_name}'"),
+            ));
+        }
         let table_id = TableId::new();
         // This is a new table, so build up its columns:
         let mut columns = Vec::new();
         let mut key = Vec::new();
-        if let Some(tag_set) = &line.series.tag_set {
-            for (tag_key, tag_val) in tag_set {
-                let col_id = ColumnId::new();
-                fields.push(Field::new(col_id, FieldData::Tag(tag_val.to_string())));
-                columns.push((col_id, Arc::from(tag_key.as_str()), InfluxColumnType::Tag));
-                // Build up the series key from the tags
-                key.push(col_id);
+        for tag in tags {
+            if skip_empty_tag(line, line_number, table_name, tag, batch_state) {
+                continue;
+            }
+            check_column_name(line, line_number, tag.key, config)?;
+            let col_id = ColumnId::new();
+            if check_tag_cardinality(line, line_number, col_id, tag, config, batch_state)? {
+                new_tag_values.push((col_id, tag.value));
+            }
+            fields.push(Field::new(col_id, FieldData::Tag(tag.value.to_string())));
+            columns.push((col_id, Arc::from(tag.key), InfluxColumnType::Tag));
+            // Build up the series key from the tags
+            key.push(col_id);
+            if tag.from_line {
                 index_count += 1;
             }
         }
         for (field_name, field_val) in &line.field_set {
             let col_id = ColumnId::new();
-            columns.push((
-                col_id,
-                Arc::from(field_name.as_str()),
-                influx_column_type_from_field_value(field_val),
-            ));
-            fields.push(Field::new(col_id, field_val));
+            let (col_type, value) = new_column_value(
+                line,
+                line_number,
+                field_name.as_str(),
+                field_val,
+                config,
+                batch_state,
+            )?;
+            columns.push((col_id, Arc::from(field_name.as_str()), col_type));
+            fields.push(Field::new(col_id, value));
             field_count += 1;
         }
         // Always add time last on new table:
@@ -327,11 +1882,18 @@ fn validate_and_qualify_line(
             Arc::from(TIME_COLUMN_NAME),
             InfluxColumnType::Timestamp,
         ));
-        let timestamp_ns = line
-            .timestamp
-            .map(|ts| apply_precision_to_timestamp(precision, ts))
-            .unwrap_or(ingest_time.timestamp_nanos());
+        let (timestamp_ns, applied_precision) = qualify_timestamp(
+            line,
+            line_number,
+            ingest_time_ns,
+            precision,
+            config,
+            batch_state,
+        )?;
         fields.push(Field::new(time_col_id, FieldData::Timestamp(timestamp_ns)));
+        if !keep_row(line, line_number, fields.len() > 1, config, batch_state)? {
+            return Ok(None);
+        }
 
         let table_name = table_name.into();
         let mut field_definitions = Vec::with_capacity(columns.len());
@@ -346,9 +1908,24 @@ fn validate_and_qualify_line(
             table_name: Arc::clone(&table_name),
             field_definitions,
             key: key.clone(),
+            explicit_series_key: config.explicit_series_keys,
         }));
 
-        let table = TableDefinition::new(table_id, Arc::clone(&table_name), columns, key).unwrap();
+        let mut table = TableDefinition::new(table_id, Arc::clone(&table_name), columns, key)
+            .map_err(|e| WriteLineError {
+                original_line: line.to_string(),
+                line_number: line_number + 1,
+                error_message: format!(
+                    "could not create table '{table_name}' for line {line_number}: {e}",
+                    line_number = line_number + 1,
+                ),
+            })?;
+        table.explicit_series_key = config.explicit_series_keys;
+        let row = Row {
+            time: timestamp_ns,
+            fields,
+        };
+        validate_row(line, line_number, &row, &table, config)?;
 
         let db_schema = db_schema.to_mut();
         db_schema
@@ -368,210 +1945,5317 @@ fn validate_and_qualify_line(
                     })
                 },
             )?;
+        if let (false, Some(TableCreatedCallback(on_table_created))) =
+            (batch_state.check_only, &config.on_table_created)
+        {
+            (on_table_created.lock())(table_id, &table_name);
+        }
         QualifiedLine {
             table_id,
-            row: Row {
-                time: timestamp_ns,
-                fields,
-            },
+            row,
             index_count,
             field_count,
+            series_key_bytes: None,
+            row_hash: None,
+            timestamp_was_defaulted,
+            table_created: true,
+            original_line: None,
+            effective_precision: applied_precision
+                .filter(|_| config.effective_precisions)
+                .map(|precision| (line_number + 1, precision)),
         }
     };
 
-    Ok((qualified, catalog_op))
-}
+    if config.series_keys || config.row_hashes {
+        let table_def = db_schema
+            .table_definition_by_id(&qualified.table_id)
+            .expect("table was added to the schema for this line");
+        if config.series_keys {
+            qualified.series_key_bytes = Some(encode_series_key(&table_def, &qualified.row.fields));
+        }
+        if config.row_hashes {
+            qualified.row_hash = Some(row_hash(&table_def, &qualified.row));
+        }
+    }
 
-/// Result of conversion from line protocol to valid chunked data
-/// for the buffer.
-#[derive(Debug)]
-pub struct ValidatedLines {
-    /// Number of lines passed in
-    pub(crate) line_count: usize,
-    /// Number of bytes of all valid lines written
-    pub(crate) valid_bytes_count: u64,
-    /// Number of fields passed in
-    pub(crate) field_count: usize,
-    /// Number of index columns passed in, whether tags (v1) or series keys (v3)
-    pub(crate) index_count: usize,
-    /// Any errors that occurred while parsing the lines
-    pub errors: Vec<WriteLineError>,
-    /// Only valid lines will be converted into a WriteBatch
-    pub valid_data: WriteBatch,
-    /// If any catalog updates were made, they will be included here
-    pub(crate) catalog_updates: Option<OrderedCatalogBatch>,
+    if config.column_size_accounting {
+        for field in &qualified.row.fields {
+            if let Some(size) = field_value_size(&field.value) {
+                *batch_state.per_column_bytes.entry(field.id).or_default() += size;
+            }
+        }
+    }
+    Ok(Some((qualified, catalog_op)))
 }
 
-impl From<ValidatedLines> for WriteBatch {
-    fn from(value: ValidatedLines) -> Self {
-        value.valid_data
+/// Encode the series key of a row in the given table
+///
+/// The tags in the table's series key are encoded in the order of the series key for tables
+/// created with an explicit series key (v3), and sorted by name for tables whose series key is
+/// made up of the tags written to them (v1), whose series key order is only the order the tags
+/// were first written in. Each tag is encoded as the length of its name as a little-endian
+/// `u32`, the name, the length of its value as a little-endian `u32`, and the value. Tags in
+/// the series key that the row does not have a value for are omitted, so any two rows in the
+/// same series produce identical bytes.
+pub fn encode_series_key(table_def: &TableDefinition, fields: &[Field]) -> Vec<u8> {
+    let mut tags = Vec::with_capacity(table_def.series_key_ids().len());
+    for col_id in table_def.series_key_ids() {
+        let Some(value) = fields.iter().find_map(|f| match &f.value {
+            FieldData::Tag(v) | FieldData::Key(v) if f.id == *col_id => Some(v),
+            _ => None,
+        }) else {
+            continue;
+        };
+        tags.push((table_def.column_id_to_name_unchecked(col_id), value));
+    }
+    if !table_def.explicit_series_key {
+        tags.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
     }
+    let mut bytes = Vec::new();
+    for (name, value) in tags {
+        bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(value.as_bytes());
+    }
+    bytes
 }
 
-impl WriteValidator<LinesParsed> {
-    /// Convert this into the inner [`LinesParsed`]
-    ///
-    /// This is mainly used for testing
-    pub fn into_inner(self) -> LinesParsed {
-        self.state
+/// The tables and columns that are in one version of a [`DatabaseSchema`] but not another, as
+/// computed by [`schema_diff`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaDiff {
+    /// The names of the added tables, in the order of the tables in the schema
+    pub added_tables: Vec<Arc<str>>,
+    /// The name and type of each added column, by table name, for both added and existing
+    /// tables, in the order of the columns in each table
+    pub added_columns: IndexMap<Arc<str>, Vec<(Arc<str>, InfluxColumnType)>>,
+}
+
+impl SchemaDiff {
+    /// Whether no tables or columns were added
+    pub fn is_empty(&self) -> bool {
+        self.added_tables.is_empty() && self.added_columns.is_empty()
     }
+}
 
-    /// Convert a set of valid parsed lines to a [`ValidatedLines`] which will
-    /// be buffered and written to the WAL, if configured.
-    ///
-    /// This involves splitting out the writes into different batches for each chunk, which will
-    /// map to the `Gen1Duration`. This function should be infallible, because
-    /// the schema for incoming writes has been fully validated.
-    pub fn convert_lines_to_buffer(self, gen1_duration: Gen1Duration) -> ValidatedLines {
-        let mut table_chunks = IndexMap::new();
-        let line_count = self.state.lines.len();
-        let mut field_count = 0;
-        let mut index_count = 0;
+/// Compute the tables and columns that are in the `after` schema but not in the `before`
+/// schema, e.g., to record the schema changes made by a write in an audit log
+///
+/// Tables and columns are matched by name, so this works for schemas from different sources,
+/// such as a schema before and after several writes have been applied to the catalog.
+/// Tables and columns that were removed are not included.
+pub fn schema_diff(before: &DatabaseSchema, after: &DatabaseSchema) -> SchemaDiff {
+    let mut diff = SchemaDiff::default();
+    for table_def in after.tables() {
+        let before_table = before.table_definition(Arc::clone(&table_def.table_name));
+        if before_table.is_none() {
+            diff.added_tables.push(Arc::clone(&table_def.table_name));
+        }
+        let added_columns = table_def
+            .columns
+            .values()
+            .filter(|col_def| {
+                before_table
+                    .as_ref()
+                    .is_none_or(|t| t.column_name_to_id(Arc::clone(&col_def.name)).is_none())
+            })
+            .map(|col_def| (Arc::clone(&col_def.name), col_def.data_type))
+            .collect::<Vec<_>>();
+        if !added_columns.is_empty() {
+            diff.added_columns
+                .insert(Arc::clone(&table_def.table_name), added_columns);
+        }
+    }
+    diff
+}
 
-        for line in self.state.lines.into_iter() {
-            field_count += line.field_count;
-            index_count += line.index_count;
+/// A fixed seed for row hashes, so that rows hash identically across processes and versions
+const ROW_HASH_SEED: &[u8] = b"influxdb3-row-hash-v1";
 
-            convert_qualified_line(line, &mut table_chunks, gen1_duration);
-        }
+/// Compute a stable hash of a row in the given table
+///
+/// The hash covers the table name, the series key as encoded by [`encode_series_key`], the
+/// timestamp, and the field values in order of their column names. It uses column names rather
+/// than ids, and a fixed seed, so that identical rows produce the same hash in any process:
+/// the same line written twice hashes the same regardless of the order of its fields.
+pub fn row_hash(table_def: &TableDefinition, row: &Row) -> u64 {
+    fn update_len_prefixed(hasher: &mut Sha256, bytes: &[u8]) {
+        hasher.update((bytes.len() as u32).to_le_bytes());
+        hasher.update(bytes);
+    }
 
-        let write_batch = WriteBatch::new(
-            self.state.catalog.db_schema.id,
-            Arc::clone(&self.state.catalog.db_schema.name),
-            table_chunks,
-        );
+    let mut hasher = Sha256::new();
+    hasher.update(ROW_HASH_SEED);
+    update_len_prefixed(&mut hasher, table_def.table_name.as_bytes());
+    update_len_prefixed(&mut hasher, &encode_series_key(table_def, &row.fields));
+    hasher.update(row.time.to_le_bytes());
 
-        ValidatedLines {
-            line_count,
-            valid_bytes_count: self.state.bytes,
-            field_count,
-            index_count,
-            errors: self.state.errors,
-            valid_data: write_batch,
-            catalog_updates: self.state.catalog_batch,
+    let mut values = row
+        .fields
+        .iter()
+        .filter(|f| {
+            !matches!(
+                f.value,
+                FieldData::Tag(_) | FieldData::Key(_) | FieldData::Timestamp(_)
+            )
+        })
+        .map(|f| (table_def.column_id_to_name_unchecked(&f.id), &f.value))
+        .collect::<Vec<_>>();
+    values.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    for (name, value) in values {
+        update_len_prefixed(&mut hasher, name.as_bytes());
+        match value {
+            FieldData::String(v) => {
+                hasher.update([0u8]);
+                update_len_prefixed(&mut hasher, v.as_bytes());
+            }
+            FieldData::Integer(v) => {
+                hasher.update([1u8]);
+                hasher.update(v.to_le_bytes());
+            }
+            FieldData::UInteger(v) => {
+                hasher.update([2u8]);
+                hasher.update(v.to_le_bytes());
+            }
+            FieldData::Float(v) => {
+                hasher.update([3u8]);
+                hasher.update(v.to_bits().to_le_bytes());
+            }
+            FieldData::Boolean(v) => {
+                hasher.update([4u8]);
+                hasher.update([*v as u8]);
+            }
+            FieldData::Tag(_) | FieldData::Key(_) | FieldData::Timestamp(_) => {
+                unreachable!("filtered out above")
+            }
         }
     }
-}
 
-fn convert_qualified_line(
-    line: QualifiedLine,
-    table_chunk_map: &mut IndexMap<TableId, TableChunks>,
-    gen1_duration: Gen1Duration,
-) {
-    // Add the row into the correct chunk in the table
-    let chunk_time = gen1_duration.chunk_time_for_timestamp(Timestamp::new(line.row.time));
-    let table_chunks = table_chunk_map.entry(line.table_id).or_default();
-    table_chunks.push_row(chunk_time, line.row);
+    let digest = hasher.finalize();
+    u64::from_le_bytes(digest[..8].try_into().expect("digest has at least 8 bytes"))
 }
 
-#[derive(Debug)]
-struct QualifiedLine {
-    table_id: TableId,
-    row: Row,
-    index_count: usize,
-    field_count: usize,
+/// Compute a checksum of the rows in a [`WriteBatch`], to check the integrity of a write
+/// between ingest and storage
+///
+/// Each row is checksummed with CRC32 over its database, table, and fields in order of their
+/// column IDs, and the row checksums are summed, so the result does not depend on the order
+/// that the rows were written in, or the order of their fields.
+pub fn write_batch_checksum(batch: &WriteBatch) -> u64 {
+    fn update_len_prefixed(hasher: &mut crc32fast::Hasher, bytes: &[u8]) {
+        hasher.update(&(bytes.len() as u32).to_le_bytes());
+        hasher.update(bytes);
+    }
+
+    let mut checksum = 0u64;
+    for (table_id, table_chunks) in &batch.table_chunks {
+        for chunk in table_chunks.chunk_time_to_chunk.values() {
+            for row in &chunk.rows {
+                let mut hasher = crc32fast::Hasher::new();
+                hasher.update(&batch.database_id.as_u32().to_le_bytes());
+                hasher.update(&table_id.as_u32().to_le_bytes());
+                hasher.update(&row.time.to_le_bytes());
+                let mut fields = row.fields.iter().collect::<Vec<_>>();
+                fields.sort_unstable_by_key(|f| f.id);
+                for field in fields {
+                    hasher.update(&field.id.as_u32().to_le_bytes());
+                    match &field.value {
+                        FieldData::Timestamp(v) => {
+                            hasher.update(&[0]);
+                            hasher.update(&v.to_le_bytes());
+                        }
+                        FieldData::Key(v) => {
+                            hasher.update(&[1]);
+                            update_len_prefixed(&mut hasher, v.as_bytes());
+                        }
+                        FieldData::Tag(v) => {
+                            hasher.update(&[2]);
+                            update_len_prefixed(&mut hasher, v.as_bytes());
+                        }
+                        FieldData::String(v) => {
+                            hasher.update(&[3]);
+                            update_len_prefixed(&mut hasher, v.as_bytes());
+                        }
+                        FieldData::Integer(v) => {
+                            hasher.update(&[4]);
+                            hasher.update(&v.to_le_bytes());
+                        }
+                        FieldData::UInteger(v) => {
+                            hasher.update(&[5]);
+                            hasher.update(&v.to_le_bytes());
+                        }
+                        FieldData::Float(v) => {
+                            hasher.update(&[6]);
+                            hasher.update(&v.to_bits().to_le_bytes());
+                        }
+                        FieldData::Boolean(v) => {
+                            hasher.update(&[7]);
+                            hasher.update(&[*v as u8]);
+                        }
+                    }
+                }
+                checksum = checksum.wrapping_add(u64::from(hasher.finalize()));
+            }
+        }
+    }
+    checksum
 }
 
-fn apply_precision_to_timestamp(precision: Precision, ts: i64) -> i64 {
-    let multiplier = match precision {
-        Precision::Auto => match crate::guess_precision(ts) {
-            Precision::Second => 1_000_000_000,
-            Precision::Millisecond => 1_000_000,
-            Precision::Microsecond => 1_000,
-            Precision::Nanosecond => 1,
+/// The size in bytes of a field value for column size accounting
+///
+/// String values count their length in bytes, while other values count their fixed size.
+/// The timestamp is not counted, since every row has one.
+fn field_value_size(value: &FieldData) -> Option<u64> {
+    match value {
+        FieldData::Timestamp(_) => None,
+        FieldData::Key(s) | FieldData::Tag(s) | FieldData::String(s) => Some(s.len() as u64),
+        FieldData::Integer(_) | FieldData::UInteger(_) | FieldData::Float(_) => Some(8),
+        FieldData::Boolean(_) => Some(1),
+    }
+}
 
-            Precision::Auto => unreachable!(),
-        },
-        Precision::Second => 1_000_000_000,
-        Precision::Millisecond => 1_000_000,
-        Precision::Microsecond => 1_000,
-        Precision::Nanosecond => 1,
+/// Check that a tag value does not exceed the cardinality budget of its column, if it has one,
+/// returning whether it is a new value that counts against the budget
+///
+/// The value is not recorded here, since the line may still be rejected or dropped, so new
+/// values are recorded with [`record_tag_values`] once the line is accepted.
+fn check_tag_cardinality(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    col_id: ColumnId,
+    tag: &LineTag<'_>,
+    config: &ValidatorConfig,
+    batch_state: &BatchState,
+) -> Result<bool, WriteLineError> {
+    let Some(budget) = config.tag_cardinality_budgets.get(tag.key) else {
+        return Ok(false);
     };
+    let values = batch_state.tag_values.get(&col_id);
+    if values.is_some_and(|values| values.contains(tag.value)) {
+        return Ok(false);
+    }
+    if values.map_or(0, HashSet::len) >= *budget {
+        return Err(WriteLineError {
+            original_line: line.to_string(),
+            line_number: line_number + 1,
+            error_message: format!(
+                "tag '{key}' on line {line_number} exceeds its budget of {budget} distinct \
+                values in a single write",
+                key = tag.key,
+                line_number = line_number + 1,
+            ),
+        });
+    }
+    Ok(true)
+}
 
-    ts * multiplier
+/// Record the new values of tags with a cardinality budget for a line that was accepted
+fn record_tag_values(new_tag_values: &mut Vec<(ColumnId, &str)>, batch_state: &mut BatchState) {
+    for (col_id, value) in new_tag_values.drain(..) {
+        batch_state
+            .tag_values
+            .entry(col_id)
+            .or_default()
+            .insert(value.to_string());
+    }
 }
 
-#[cfg(test)]
+/// Check whether the row for a line has content other than its timestamp, returning whether
+/// the row should be kept, or an error if the line is rejected
+fn keep_row(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    has_content: bool,
+    config: &ValidatorConfig,
+    batch_state: &mut BatchState,
+) -> Result<bool, WriteLineError> {
+    if has_content {
+        return Ok(true);
+    }
+    let message = format!(
+        "line {line_number} has no tags or fields",
+        line_number = line_number + 1
+    );
+    match config.empty_row_policy {
+        EmptyRowPolicy::Reject => Err(WriteLineError {
+            original_line: line.to_string(),
+            line_number: line_number + 1,
+            error_message: message,
+        }),
+        EmptyRowPolicy::Skip => {
+            // the line is not written, but the warning is reported along with those of the
+            // accepted lines:
+            batch_state.warnings.push(WriteLineWarning {
+                original_line: line.to_string(),
+                line_number: line_number + 1,
+                warning_message: format!("{message}, so was skipped"),
+            });
+            Ok(false)
+        }
+    }
+}
+
+/// Check whether a line that is not compatible with the schema should be dropped, rather than
+/// rejected, counting the line if so
+fn drop_incompatible(config: &ValidatorConfig, batch_state: &mut BatchState) -> bool {
+    match config.incompatible_line_mode {
+        IncompatibleLineMode::Strict => false,
+        IncompatibleLineMode::DropIncompatible => {
+            batch_state.dropped_incompatible_count += 1;
+            true
+        }
+    }
+}
+
+/// The error for a line that would require a change to the catalog on a read-only validator
+fn catalog_change_error(line: &ParsedLine<'_>, line_number: usize, change: &str) -> WriteLineError {
+    WriteLineError {
+        original_line: line.to_string(),
+        line_number: line_number + 1,
+        error_message: format!(
+            "line {line_number} would require a catalog change to {change}, which is not \
+            allowed on a read-only schema snapshot",
+            line_number = line_number + 1,
+        ),
+    }
+}
+
+/// Convert a field value to the value that is written for it, checking the size of string
+/// values against the configured maximum
+fn field_data(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    field_name: &str,
+    field_val: &FieldValue<'_>,
+    config: &ValidatorConfig,
+    batch_state: &mut BatchState,
+) -> Result<FieldData, WriteLineError> {
+    let (Some((max_bytes, policy)), FieldValue::String(value)) =
+        (config.max_string_field_bytes, field_val)
+    else {
+        return Ok(field_val.into());
+    };
+    let value = value.as_str();
+    if value.len() <= max_bytes {
+        return Ok(field_val.into());
+    }
+    match policy {
+        StringLengthPolicy::Reject => Err(WriteLineError {
+            original_line: line.to_string(),
+            line_number: line_number + 1,
+            error_message: format!(
+                "string field '{field_name}' on line {line_number} is {size} bytes, which \
+                exceeds the maximum of {max_bytes} bytes",
+                line_number = line_number + 1,
+                size = value.len(),
+            ),
+        }),
+        StringLengthPolicy::Truncate => {
+            let mut end = max_bytes;
+            while !value.is_char_boundary(end) {
+                end -= 1;
+            }
+            batch_state.line.warnings.push(WriteLineWarning {
+                original_line: line.to_string(),
+                line_number: line_number + 1,
+                warning_message: format!(
+                    "string field '{field_name}' on line {line_number} was truncated from \
+                    {size} to {end} bytes",
+                    line_number = line_number + 1,
+                    size = value.len(),
+                ),
+            });
+            Ok(FieldData::String(value[..end].to_string()))
+        }
+    }
+}
+
+/// Determine the type of a new column for a field, and the value that is written to it
+///
+/// The column has the type hinted for it, if any, otherwise the type of the value. A value
+/// that differs from the hinted type is converted as it would be for an existing column.
+fn new_column_value(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    field_name: &str,
+    field_val: &FieldValue<'_>,
+    config: &ValidatorConfig,
+    batch_state: &mut BatchState,
+) -> Result<(InfluxColumnType, FieldData), WriteLineError> {
+    check_column_name(line, line_number, field_name, config)?;
+    let field_col_type = influx_column_type_from_field_value(field_val);
+    match config.column_type_hints.get(field_name) {
+        Some(hint) if *hint != field_col_type => {
+            let value = coerce_field_value(
+                line,
+                line_number,
+                field_name,
+                field_val,
+                *hint,
+                config,
+                batch_state,
+            )?;
+            Ok((*hint, value))
+        }
+        _ => {
+            let value = field_data(
+                line,
+                line_number,
+                field_name,
+                field_val,
+                config,
+                batch_state,
+            )?;
+            Ok((field_col_type, value))
+        }
+    }
+}
+
+/// Check that the name of a new column matches the configured pattern, if any
+fn check_column_name(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    column_name: &str,
+    config: &ValidatorConfig,
+) -> Result<(), WriteLineError> {
+    match &config.column_name_pattern {
+        Some(pattern) if !pattern.is_match(column_name) => Err(WriteLineError {
+            original_line: line.to_string(),
+            line_number: line_number + 1,
+            error_message: format!(
+                "column '{column_name}' on line {line_number} would be created with a name \
+                that does not match the pattern '{pattern}'",
+                line_number = line_number + 1,
+            ),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Check that the type hinted for a column, if any, matches the type of the column
+fn check_column_type_hint(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    column_name: &str,
+    col_type: InfluxColumnType,
+    config: &ValidatorConfig,
+) -> Result<(), WriteLineError> {
+    match config.column_type_hints.get(column_name) {
+        Some(hint) if *hint != col_type => Err(WriteLineError {
+            original_line: line.to_string(),
+            line_number: line_number + 1,
+            error_message: format!(
+                "column '{column_name}' on line {line_number} has type {col_type}, which \
+                conflicts with the type hint {hint} for it",
+                line_number = line_number + 1,
+            ),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Attempt to convert a field value to the type of the existing column it is written to,
+/// producing an error if the configuration does not allow the conversion
+fn coerce_field_value(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    field_name: &str,
+    field_val: &FieldValue<'_>,
+    existing_col_type: InfluxColumnType,
+    config: &ValidatorConfig,
+    batch_state: &mut BatchState,
+) -> Result<FieldData, WriteLineError> {
+    let field_col_type = influx_column_type_from_field_value(field_val);
+    let error = |error_message| WriteLineError {
+        original_line: line.to_string(),
+        line_number: line_number + 1,
+        error_message,
+    };
+    match (field_val, existing_col_type) {
+        (FieldValue::F64(v), InfluxColumnType::Field(InfluxFieldType::Integer))
+            if config.integral_floats_as_integers
+                || config.float_to_int_policy != FloatToIntPolicy::Error =>
+        {
+            let accepted_integral = v.fract() == 0.0 && config.integral_floats_as_integers;
+            let repaired = match config.float_to_int_policy {
+                _ if accepted_integral => Some(*v),
+                FloatToIntPolicy::Error => None,
+                FloatToIntPolicy::Truncate => Some(v.trunc()),
+                FloatToIntPolicy::Round => Some(v.round()),
+            };
+            // i64::MAX is not representable as a float, and rounds up to 2^63:
+            let Some(repaired) = repaired.filter(|r| *r >= i64::MIN as f64 && *r < i64::MAX as f64)
+            else {
+                return Err(error(format!(
+                    "invalid field value in line protocol for field '{field_name}' on line \
+                    {line_number}: expected type {existing_col_type}, but got float value {v} \
+                    that cannot be converted to an integer",
+                )));
+            };
+            let repaired = repaired as i64;
+            if !accepted_integral {
+                batch_state.line.warnings.push(WriteLineWarning {
+                    original_line: line.to_string(),
+                    line_number: line_number + 1,
+                    warning_message: format!(
+                        "float value {v} for integer field '{field_name}' on line \
+                        {line_number} was converted to {repaired}",
+                        line_number = line_number + 1,
+                    ),
+                });
+            }
+            Ok(FieldData::Integer(repaired))
+        }
+        // integers are only converted to floats for columns hinted to be floats:
+        (FieldValue::I64(v), InfluxColumnType::Field(InfluxFieldType::Float))
+            if config.column_type_hints.contains_key(field_name) =>
+        {
+            Ok(FieldData::Float(*v as f64))
+        }
+        (FieldValue::U64(v), InfluxColumnType::Field(InfluxFieldType::Float))
+            if config.column_type_hints.contains_key(field_name) =>
+        {
+            Ok(FieldData::Float(*v as f64))
+        }
+        _ => Err(error(format!(
+            "invalid field value in line protocol for field '{field_name}' on line \
+            {line_number}: expected type {expected}, but got {got}",
+            expected = existing_col_type,
+            got = field_col_type,
+        ))),
+    }
+}
+
+/// A tag to be written for a line, either from the line itself or added by the validator
+#[derive(Debug)]
+struct LineTag<'a> {
+    key: &'a str,
+    value: &'a str,
+    /// Whether the tag was written by the client, as opposed to added by the validator
+    from_line: bool,
+}
+
+/// Copy the default tags of a database from its schema
+fn default_tags(db_schema: &DatabaseSchema) -> Vec<(Arc<str>, Arc<str>)> {
+    db_schema
+        .default_tags
+        .iter()
+        .map(|(key, value)| (Arc::clone(key), Arc::clone(value)))
+        .collect()
+}
+
+/// Split the measurement name of a line with the configured measurement splitter, if any
+fn split_measurement(line: &ParsedLine<'_>, config: &ValidatorConfig) -> Option<SplitMeasurement> {
+    config
+        .measurement_splitter
+        .as_ref()
+        .map(|MeasurementSplitter(splitter)| splitter(line.series.measurement.as_str()))
+}
+
+/// The measurement name and tags to write a line with, which are those split from its
+/// measurement name if it was split
+fn split_measurement_parts<'a>(
+    line: &'a ParsedLine<'_>,
+    split: &'a Option<SplitMeasurement>,
+) -> (&'a str, &'a [(Arc<str>, Arc<str>)]) {
+    match split {
+        Some((measurement, tags)) => (measurement, tags),
+        None => (line.series.measurement.as_str(), &[]),
+    }
+}
+
+/// Gather the tags to be written for a line, which are the tags in the line followed by any
+/// tags split from its measurement name that the line does not set, then any injected tags,
+/// which take precedence over tags of the same name in the line, and then any default tags of
+/// the database that none of the others set
+fn line_tags<'a>(
+    tags: &mut Vec<LineTag<'a>>,
+    line: &'a ParsedLine<'_>,
+    split_tags: &'a [(Arc<str>, Arc<str>)],
+    config: &'a ValidatorConfig,
+) {
+    tags.reserve(
+        line.series.tag_set.as_ref().map_or(0, |t| t.len())
+            + split_tags.len()
+            + config.injected_tags.len()
+            + config.default_tags.len(),
+    );
+    if let Some(tag_set) = &line.series.tag_set {
+        for (tag_key, tag_val) in tag_set {
+            let key = tag_key.as_str();
+            if config.injected_tags.iter().any(|(k, _)| k.as_ref() == key) {
+                continue;
+            }
+            tags.push(LineTag {
+                key,
+                value: tag_val.as_str(),
+                from_line: true,
+            });
+        }
+    }
+    for (key, value) in split_tags {
+        if config.injected_tags.iter().any(|(k, _)| k == key)
+            || tags.iter().any(|tag| tag.key == key.as_ref())
+        {
+            continue;
+        }
+        tags.push(LineTag {
+            key,
+            value,
+            from_line: true,
+        });
+    }
+    for (key, value) in &config.injected_tags {
+        tags.push(LineTag {
+            key,
+            value,
+            from_line: false,
+        });
+    }
+    for (key, value) in &config.default_tags {
+        if tags.iter().any(|tag| tag.key == key.as_ref()) {
+            continue;
+        }
+        tags.push(LineTag {
+            key,
+            value,
+            from_line: false,
+        });
+    }
+}
+
+/// Check that none of the tags or fields of a line are named after the time column, which is
+/// reserved for the timestamp of the line
+///
+/// Such a tag or field would otherwise be written to the time column of an existing table, or
+/// create a second column with the same name as the time column of a new table.
+fn check_time_column_name(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    tags: &[LineTag<'_>],
+) -> Result<(), WriteLineError> {
+    let kind = if tags.iter().any(|tag| tag.key == TIME_COLUMN_NAME) {
+        "tag"
+    } else if line
+        .field_set
+        .iter()
+        .any(|(name, _)| name.as_str() == TIME_COLUMN_NAME)
+    {
+        "field"
+    } else {
+        return Ok(());
+    };
+    Err(WriteLineError {
+        original_line: line.to_string(),
+        line_number: line_number + 1,
+        error_message: format!(
+            "{kind} '{TIME_COLUMN_NAME}' on line {line_number} has the name of the time column, \
+            which is reserved for the timestamp of the line",
+            line_number = line_number + 1,
+        ),
+    })
+}
+
+/// Check that no column name is used more than once by the tags and fields of a line
+///
+/// The parser does not reject these, but they would map more than one value to the same
+/// column, which the catalog cannot represent.
+fn check_duplicate_columns(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    tags: &[LineTag<'_>],
+) -> Result<(), WriteLineError> {
+    let names = || {
+        tags.iter()
+            .map(|tag| tag.key)
+            .chain(line.field_set.iter().map(|(name, _)| name.as_str()))
+    };
+    let column_count = tags.len() + line.field_set.len();
+    // comparing every pair of names avoids allocating a set for the typical line with few
+    // columns:
+    let duplicate = if column_count <= 32 {
+        names()
+            .enumerate()
+            .find(|(i, name)| names().take(*i).any(|n| n == *name))
+            .map(|(_, name)| name)
+    } else {
+        let mut seen = HashSet::with_capacity(column_count);
+        names().find(|name| !seen.insert(*name))
+    };
+    match duplicate {
+        Some(name) => Err(WriteLineError {
+            original_line: line.to_string(),
+            line_number: line_number + 1,
+            error_message: format!(
+                "column '{name}' is used more than once on line {line_number}",
+                line_number = line_number + 1,
+            ),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Find the tags that have a non-empty value in at least one line of the line protocol, by
+/// the name of the table that the line is written to
+///
+/// Lines that cannot be parsed, or whose measurement name is rejected, are ignored, since they
+/// are reported when they are validated.
+fn non_empty_tags(lp: &str, config: &ValidatorConfig) -> HashMap<String, HashSet<String>> {
+    let mut non_empty_tags: HashMap<String, HashSet<String>> = HashMap::new();
+    // any warnings for the measurement names are produced when the lines are validated:
+    let mut batch_state = BatchState::default();
+    for (line_idx, raw_line) in split_lines(lp) {
+        let Some(Ok(line)) = parse_lines(raw_line).next() else {
+            continue;
+        };
+        let split = split_measurement(&line, config);
+        let (measurement, split_tags) = split_measurement_parts(&line, &split);
+        let Ok(table_name) =
+            qualify_measurement_name(&line, measurement, line_idx, config, &mut batch_state)
+        else {
+            continue;
+        };
+        let mut tags = Vec::new();
+        line_tags(&mut tags, &line, split_tags, config);
+        for tag in tags {
+            if !tag.value.is_empty() {
+                non_empty_tags
+                    .entry_ref(table_name.as_ref())
+                    .or_default()
+                    .insert(tag.key.to_string());
+            }
+        }
+    }
+    non_empty_tags
+}
+
+/// Whether a tag that would create a column is skipped because its value is empty in every
+/// line of the write, producing a warning the first time that each tag is skipped
+fn skip_empty_tag(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    table_name: &str,
+    tag: &LineTag<'_>,
+    batch_state: &mut BatchState,
+) -> bool {
+    let Some(non_empty_tags) = &batch_state.non_empty_tags else {
+        return false;
+    };
+    if !tag.value.is_empty()
+        || non_empty_tags
+            .get(table_name)
+            .is_some_and(|tags| tags.contains(tag.key))
+    {
+        return false;
+    }
+    let skipped = (table_name.to_string(), tag.key.to_string());
+    if !batch_state.skipped_empty_tags.contains(&skipped) {
+        batch_state.line.skipped_empty_tags.push(skipped);
+        batch_state.line.warnings.push(WriteLineWarning {
+            original_line: line.to_string(),
+            line_number: line_number + 1,
+            warning_message: format!(
+                "tag '{tag}' on line {line_number} is empty in every line of the write, so no \
+                column was created for it",
+                tag = tag.key,
+                line_number = line_number + 1,
+            ),
+        });
+    }
+    true
+}
+
+/// Check that a line for an existing table declares the series key explicitly only if the
+/// table was created with an explicit series key
+///
+/// A table whose series key is made up of the tags written to it by v1 clients has no declared
+/// series key that the tags of a line from a client with v3 semantics could be checked against.
+fn check_series_key_semantics(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    table_def: &TableDefinition,
+    config: &ValidatorConfig,
+) -> Result<(), WriteLineError> {
+    if !config.explicit_series_keys || table_def.explicit_series_key {
+        return Ok(());
+    }
+    Err(WriteLineError {
+        original_line: line.to_string(),
+        line_number: line_number + 1,
+        error_message: format!(
+            "line {line_number} declares an explicit series key for table '{table_name}', \
+            whose series key is made up of the tags written to it",
+            line_number = line_number + 1,
+            table_name = table_def.table_name,
+        ),
+    })
+}
+
+/// The error for a tag that is not in the series key of a table whose series key was declared
+/// when the table was created, which the tag cannot be added to
+fn tag_not_in_series_key(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    table_def: &TableDefinition,
+    tag: &LineTag<'_>,
+) -> WriteLineError {
+    WriteLineError {
+        original_line: line.to_string(),
+        line_number: line_number + 1,
+        error_message: format!(
+            "tag '{tag_key}' on line {line_number} is not in the series key of table \
+            '{table_name}', which was declared when the table was created: {series_key}",
+            tag_key = tag.key,
+            line_number = line_number + 1,
+            table_name = table_def.table_name,
+            series_key = table_def.series_key_names().join(", "),
+        ),
+    }
+}
+
+/// Check whether a line for an existing table has none of the tags in the table's series key,
+/// against the configured [`SeriesKeyMissingPolicy`], returning whether the series key tags
+/// should be given empty values
+fn series_key_missing(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    table_def: &TableDefinition,
+    tags: &[LineTag<'_>],
+    config: &ValidatorConfig,
+) -> Result<bool, WriteLineError> {
+    let series_key = table_def.series_key_names();
+    if config.series_key_missing_policy == SeriesKeyMissingPolicy::Allow
+        || series_key.is_empty()
+        || tags
+            .iter()
+            .any(|tag| series_key.iter().any(|key| key.as_ref() == tag.key))
+    {
+        return Ok(false);
+    }
+    match config.series_key_missing_policy {
+        SeriesKeyMissingPolicy::Allow => unreachable!("allowed lines are returned above"),
+        SeriesKeyMissingPolicy::Reject => Err(WriteLineError {
+            original_line: line.to_string(),
+            line_number: line_number + 1,
+            error_message: format!(
+                "line {line_number} has none of the series key tags of table '{table_name}': \
+                {series_key}",
+                line_number = line_number + 1,
+                table_name = table_def.table_name,
+                series_key = series_key.join(", "),
+            ),
+        }),
+        SeriesKeyMissingPolicy::EmptyDefaults => Ok(true),
+    }
+}
+
+/// Check a row against the configured row validator, if there is one
+fn validate_row(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    row: &Row,
+    table_def: &TableDefinition,
+    config: &ValidatorConfig,
+) -> Result<(), WriteLineError> {
+    let Some(RowValidator(row_validator)) = &config.row_validator else {
+        return Ok(());
+    };
+    row_validator(row, table_def).map_err(|message| WriteLineError {
+        original_line: line.to_string(),
+        line_number: line_number + 1,
+        error_message: format!(
+            "row on line {line_number} was rejected: {message}",
+            line_number = line_number + 1,
+        ),
+    })
+}
+
+/// Produce the name of the table that a line will be written to, checking its measurement name
+/// against the configured [`MeasurementNamePolicy`] and normalizing its case according to the
+/// configured [`MeasurementCasePolicy`]
+fn qualify_measurement_name<'a>(
+    line: &ParsedLine<'_>,
+    measurement: &'a str,
+    line_number: usize,
+    config: &ValidatorConfig,
+    batch_state: &mut BatchState,
+) -> Result<Cow<'a, str>, WriteLineError> {
+    let name = check_measurement_name(line, measurement, line_number, config, batch_state)?;
+    Ok(match config.measurement_case_policy {
+        MeasurementCasePolicy::Lowercase if name.chars().any(char::is_uppercase) => {
+            Cow::Owned(name.to_lowercase())
+        }
+        _ => name,
+    })
+}
+
+/// Check the measurement name of a line against the configured [`MeasurementNamePolicy`]
+fn check_measurement_name<'a>(
+    line: &ParsedLine<'_>,
+    name: &'a str,
+    line_number: usize,
+    config: &ValidatorConfig,
+    batch_state: &mut BatchState,
+) -> Result<Cow<'a, str>, WriteLineError> {
+    if config.measurement_name_policy == MeasurementNamePolicy::Allow
+        || is_safe_measurement_name(name)
+    {
+        return Ok(Cow::Borrowed(name));
+    }
+    match config.measurement_name_policy {
+        MeasurementNamePolicy::Allow => unreachable!("allowed names are returned above"),
+        MeasurementNamePolicy::Reject => Err(WriteLineError {
+            original_line: line.to_string(),
+            line_number: line_number + 1,
+            error_message: format!(
+                "invalid measurement name '{name}' on line {line_number}: measurement names \
+                cannot contain '/', '\\', ',', or control characters, and cannot be '.' or '..'",
+                line_number = line_number + 1,
+            ),
+        }),
+        MeasurementNamePolicy::Sanitize => {
+            let sanitized = sanitize_measurement_name(name);
+            batch_state.line.warnings.push(WriteLineWarning {
+                original_line: line.to_string(),
+                line_number: line_number + 1,
+                warning_message: format!(
+                    "measurement name '{name}' on line {line_number} contains unsafe \
+                    characters, and was written to '{sanitized}' instead",
+                    line_number = line_number + 1,
+                ),
+            });
+            Ok(Cow::Owned(sanitized))
+        }
+    }
+}
+
+fn is_unsafe_measurement_char(c: char) -> bool {
+    c.is_control() || matches!(c, '/' | '\\' | ',')
+}
+
+fn is_safe_measurement_name(name: &str) -> bool {
+    name != "." && name != ".." && !name.chars().any(is_unsafe_measurement_char)
+}
+
+fn sanitize_measurement_name(name: &str) -> String {
+    if name == "." || name == ".." {
+        return "_".repeat(name.len());
+    }
+    name.chars()
+        .map(|c| {
+            if is_unsafe_measurement_char(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Produce the timestamp in nanoseconds for a line, applying the precision, or defaulting to
+/// the ingest time if the line has no timestamp, and check it against the configured bounds
+///
+/// Along with the timestamp, this returns the precision that was applied to it, or `None` if
+/// the line has no timestamp.
+fn qualify_timestamp(
+    line: &ParsedLine<'_>,
+    line_number: usize,
+    ingest_time_ns: i64,
+    precision: Precision,
+    config: &ValidatorConfig,
+    batch_state: &mut BatchState,
+) -> Result<(i64, Option<Precision>), WriteLineError> {
+    let (timestamp_ns, applied_precision) = match (line.timestamp, config.timestamp_base_ns) {
+        (Some(delta), Some(base_ns)) => {
+            let error = |error_message| WriteLineError {
+                original_line: line.to_string(),
+                line_number: line_number + 1,
+                error_message,
+            };
+            if delta < 0 {
+                return Err(error(format!(
+                    "negative timestamp delta {delta} on line {line_number} is not allowed",
+                    line_number = line_number + 1,
+                )));
+            }
+            let applied_precision = match precision {
+                Precision::Auto | Precision::AutoBatch => Precision::Nanosecond,
+                precision => precision,
+            };
+            let timestamp_ns = delta
+                .checked_mul(precision_multiplier(applied_precision, delta))
+                .and_then(|delta_ns| base_ns.checked_add(delta_ns))
+                .ok_or_else(|| {
+                    error(format!(
+                        "timestamp delta {delta} on line {line_number} overflows the base \
+                        timestamp {base_ns}",
+                        line_number = line_number + 1,
+                    ))
+                })?;
+            (timestamp_ns, Some(applied_precision))
+        }
+        (Some(ts), None) => {
+            let (timestamp_ns, applied_precision) = apply_precision_to_timestamp(precision, ts);
+            let timestamp_ns = timestamp_ns.ok_or_else(|| WriteLineError {
+                original_line: line.to_string(),
+                line_number: line_number + 1,
+                error_message: format!(
+                    "timestamp {ts} on line {line_number} is out of range when converted from \
+                    {applied_precision:?} precision to nanoseconds",
+                    line_number = line_number + 1,
+                ),
+            })?;
+            (timestamp_ns, Some(applied_precision))
+        }
+        (None, _) => (ingest_time_ns, None),
+    };
+
+    if let Some(min_ns) = config
+        .min_timestamp_ns
+        .filter(|min_ns| timestamp_ns <= *min_ns)
+    {
+        return Err(WriteLineError {
+            original_line: line.to_string(),
+            line_number: line_number + 1,
+            error_message: format!(
+                "timestamp {timestamp_ns} on line {line_number} is not after the minimum \
+                timestamp {min_ns}",
+                line_number = line_number + 1,
+            ),
+        });
+    }
+
+    if let Some((max_skew, policy)) = config.max_future_skew {
+        let skew_ns = timestamp_ns.saturating_sub(ingest_time_ns);
+        if skew_ns > 0 && skew_ns as u128 > max_skew.as_nanos() {
+            let skew = Duration::from_nanos(skew_ns as u64);
+            let message = format!(
+                "timestamp {timestamp_ns} on line {line_number} is {skew:?} ahead of the ingest \
+                time, which exceeds the maximum skew of {max_skew:?}",
+                line_number = line_number + 1,
+            );
+            match policy {
+                ClockSkewPolicy::Warn => batch_state.line.warnings.push(WriteLineWarning {
+                    original_line: line.to_string(),
+                    line_number: line_number + 1,
+                    warning_message: message,
+                }),
+                ClockSkewPolicy::Reject => {
+                    return Err(WriteLineError {
+                        original_line: line.to_string(),
+                        line_number: line_number + 1,
+                        error_message: message,
+                    });
+                }
+            }
+        }
+    }
+
+    if let Some((max_span, policy)) = config.max_timestamp_span {
+        let (min_ns, max_ns) = batch_state
+            .time_range
+            .map_or((timestamp_ns, timestamp_ns), |(min_ns, max_ns)| {
+                (min_ns.min(timestamp_ns), max_ns.max(timestamp_ns))
+            });
+        let span = Duration::from_nanos(max_ns.abs_diff(min_ns));
+        if span > max_span {
+            let message = format!(
+                "timestamp {timestamp_ns} on line {line_number} makes the write span {span:?}, \
+                which exceeds the maximum span of {max_span:?}",
+                line_number = line_number + 1,
+            );
+            match policy {
+                TimestampSpanPolicy::Warn if !batch_state.time_span_warned => {
+                    batch_state.line.time_span_warned = true;
+                    batch_state.line.warnings.push(WriteLineWarning {
+                        original_line: line.to_string(),
+                        line_number: line_number + 1,
+                        warning_message: message,
+                    });
+                }
+                TimestampSpanPolicy::Warn => (),
+                TimestampSpanPolicy::Reject => {
+                    return Err(WriteLineError {
+                        original_line: line.to_string(),
+                        line_number: line_number + 1,
+                        error_message: message,
+                    });
+                }
+            }
+        }
+        batch_state.line.time_range = Some((min_ns, max_ns));
+    }
+
+    Ok((timestamp_ns, applied_precision))
+}
+
+/// Result of conversion from line protocol to valid chunked data
+/// for the buffer.
+///
+/// The `valid_data` is produced by the [`RowSink`] the lines were converted into, which is a
+/// [`WriteBatch`] by default.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidatedLines<D = WriteBatch> {
+    /// Number of lines passed in
+    pub(crate) line_count: usize,
+    /// Number of bytes of all valid lines written
+    pub(crate) valid_bytes_count: u64,
+    /// Number of fields passed in
+    pub(crate) field_count: usize,
+    /// Number of index columns passed in, whether tags (v1) or series keys (v3)
+    pub(crate) index_count: usize,
+    /// Any errors that occurred while parsing the lines
+    pub errors: Vec<WriteLineError>,
+    /// Number of the errors that are for lines that could not be parsed, rather than lines
+    /// that failed validation
+    pub(crate) parse_error_count: usize,
+    /// Any warnings for lines that were accepted
+    pub warnings: Vec<WriteLineWarning>,
+    /// Number of lines dropped because they were not compatible with the schema, if
+    /// incompatible lines are dropped
+    pub dropped_incompatible_count: usize,
+    /// Whether the write had more than the maximum number of lines, so the lines past the
+    /// maximum were ignored
+    pub lines_truncated: bool,
+    /// The total size in bytes of values written to each column, if column size accounting
+    /// was enabled, otherwise this is empty
+    pub per_column_bytes: HashMap<ColumnId, u64>,
+    /// The table and encoded series key of each valid line, in the order the lines were
+    /// written, if series keys were enabled, otherwise this is empty
+    pub series_keys: Vec<(TableId, Vec<u8>)>,
+    /// The hash of each valid row, in the order the lines were written, if row hashes were
+    /// enabled, otherwise this is empty
+    pub row_hashes: Vec<u64>,
+    /// Number of valid lines that had no timestamp, so were given the ingest time
+    pub defaulted_timestamp_count: usize,
+    /// Number of valid lines that created a table, rather than writing to an existing one
+    pub new_table_line_count: usize,
+    /// The distinct tables written to by valid lines, in the order they were first written to
+    pub tables_touched: Vec<TableId>,
+    /// Number of valid rows for each table that were written after a row with a later
+    /// timestamp for the same table, for tables that have any such rows
+    ///
+    /// These rows are not rejected, but may need to be handled as late-arriving data, e.g.,
+    /// by sorting before persisting.
+    pub out_of_order_row_count: HashMap<TableId, usize>,
+    /// The table and original text of each valid line, in the order the lines were written,
+    /// if original lines were enabled, otherwise this is empty
+    pub original_lines: Vec<(TableId, String)>,
+    /// The line number and the precision applied to the timestamp of each valid line that has
+    /// a timestamp, in the order the lines were written, if effective precisions were enabled,
+    /// otherwise this is empty
+    pub effective_precisions: Vec<(usize, Precision)>,
+    /// The schema of the database with any tables and columns added by the write
+    ///
+    /// The schema is not serialized with the lines, as the process that receives them has its
+    /// own copy of the catalog, so it is given to [`from_bytes`][Self::from_bytes] instead.
+    #[serde(skip, default = "unattached_schema")]
+    pub validated_schema: Arc<DatabaseSchema>,
+    /// The time spent in each phase of validating the write, if phase durations were enabled
+    pub phase_durations: Option<PhaseDurations>,
+    /// Only valid lines will be converted into the sink output, e.g., a WriteBatch
+    pub valid_data: D,
+    /// If any catalog updates were made, they will be included here
+    pub(crate) catalog_updates: Option<OrderedCatalogBatch>,
+}
+
+/// The time spent in each phase of validating a write, see
+/// [`WriteValidator::with_phase_durations`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseDurations {
+    /// Parsing the lines and validating them against the schema
+    pub parse: Duration,
+    /// Applying any changes to the schema to the catalog
+    pub catalog_apply: Duration,
+    /// Organizing the rows into gen1 chunks in the sink
+    pub chunking: Duration,
+}
+
+/// A sample of the errors for a write, see [`ValidatedLines::error_sample`]
+#[derive(Debug)]
+pub struct ErrorSample<'a> {
+    /// Up to the requested number of errors, in the order of their lines
+    pub errors: Vec<SampledError<'a>>,
+    /// The total number of errors for the write
+    pub total_count: usize,
+}
+
+/// An error in an [`ErrorSample`], with the lines around it for context
+#[derive(Debug)]
+pub struct SampledError<'a> {
+    pub error: &'a WriteLineError,
+    /// The line before the line with the error, if context was requested and there is one
+    pub line_before: Option<&'a str>,
+    /// The line after the line with the error, if context was requested and there is one
+    pub line_after: Option<&'a str>,
+}
+
+impl<D> ValidatedLines<D> {
+    /// A sample of up to `n` of the errors for the write, along with the total number of errors
+    ///
+    /// This avoids returning every error for a large write that is mostly invalid. If the line
+    /// protocol of the write is given, each error includes the lines before and after its line
+    /// for context, skipping blank lines and comments.
+    pub fn error_sample<'a>(&'a self, n: usize, lp: Option<&'a str>) -> ErrorSample<'a> {
+        let lines = lp.map(|lp| split_lines(lp).collect::<Vec<_>>());
+        let errors = self
+            .errors
+            .iter()
+            .take(n)
+            .map(|error| {
+                let Some(lines) = &lines else {
+                    return SampledError {
+                        error,
+                        line_before: None,
+                        line_after: None,
+                    };
+                };
+                let line_idx = error.line_number.saturating_sub(1);
+                let pos = lines.partition_point(|(idx, _)| *idx < line_idx);
+                let after = match lines.get(pos) {
+                    Some((idx, _)) if *idx == line_idx => pos + 1,
+                    _ => pos,
+                };
+                SampledError {
+                    error,
+                    line_before: pos.checked_sub(1).map(|i| lines[i].1),
+                    line_after: lines.get(after).map(|(_, line)| *line),
+                }
+            })
+            .collect();
+        ErrorSample {
+            errors,
+            total_count: self.errors.len(),
+        }
+    }
+}
+
+#[cfg(feature = "metrics-text")]
+impl<D> ValidatedLines<D> {
+    /// Render the stats collected for this write in the Prometheus text exposition format
+    ///
+    /// The stats are exposed as gauges whose values are for this write only.
+    pub fn to_metrics_text(&self) -> String {
+        use std::fmt::Write;
+
+        let schema_changes = self
+            .catalog_updates
+            .as_ref()
+            .map_or(0, |batch| batch.batch().ops.len());
+        let metrics: [(&str, &str, &[(&str, usize)]); 8] = [
+            ("lines", "Number of valid lines", &[("", self.line_count)]),
+            (
+                "bytes",
+                "Number of bytes of all valid lines",
+                &[("", self.valid_bytes_count as usize)],
+            ),
+            (
+                "fields",
+                "Number of fields in valid lines",
+                &[("", self.field_count)],
+            ),
+            (
+                "index_columns",
+                "Number of tags or series key columns in valid lines",
+                &[("", self.index_count)],
+            ),
+            (
+                "errors",
+                "Number of lines rejected, by kind of error",
+                &[
+                    ("parse", self.parse_error_count),
+                    ("validation", self.errors.len() - self.parse_error_count),
+                ],
+            ),
+            (
+                "warnings",
+                "Number of warnings for valid lines",
+                &[("", self.warnings.len())],
+            ),
+            (
+                "defaulted_timestamps",
+                "Number of valid lines given the ingest time as their timestamp",
+                &[("", self.defaulted_timestamp_count)],
+            ),
+            (
+                "schema_changes",
+                "Number of catalog changes made by the write",
+                &[("", schema_changes)],
+            ),
+        ];
+
+        let mut text = String::new();
+        for (name, help, values) in metrics {
+            let name = format!("influxdb3_write_validated_{name}");
+            // writing to a string cannot fail:
+            writeln!(text, "# HELP {name} {help}").unwrap();
+            writeln!(text, "# TYPE {name} gauge").unwrap();
+            for (kind, value) in values {
+                if kind.is_empty() {
+                    writeln!(text, "{name} {value}").unwrap();
+                } else {
+                    writeln!(text, "{name}{{kind=\"{kind}\"}} {value}").unwrap();
+                }
+            }
+        }
+        text
+    }
+}
+
+impl<D: Serialize + DeserializeOwned> ValidatedLines<D> {
+    /// Serialize into a compact binary representation, for sending validated lines to another
+    /// process without validating them again
+    ///
+    /// This uses the same binary format as the WAL, and the result can be restored using
+    /// [`from_bytes`][Self::from_bytes].
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bitcode::serialize(self)?)
+    }
+
+    /// Deserialize validated lines from the representation produced by
+    /// [`to_bytes`][Self::to_bytes], with the schema of the database that they were validated
+    /// against, including any changes made by their catalog updates
+    pub fn from_bytes(bytes: &[u8], validated_schema: Arc<DatabaseSchema>) -> Result<Self> {
+        let lines: Self = bitcode::deserialize(bytes)?;
+        Ok(Self {
+            validated_schema,
+            ..lines
+        })
+    }
+}
+
+/// The schema that [`ValidatedLines`] are deserialized with, until the schema they were
+/// validated against is attached by [`ValidatedLines::from_bytes`]
+fn unattached_schema() -> Arc<DatabaseSchema> {
+    Arc::new(DatabaseSchema::new(DbId::from(0), Arc::from("")))
+}
+
+impl ValidatedLines {
+    /// A checksum of the valid rows of the write, as computed by [`write_batch_checksum`]
+    ///
+    /// This can be compared with the checksum of the [`WriteBatch`] once it is persisted, to
+    /// detect corruption.
+    pub fn content_checksum(&self) -> u64 {
+        write_batch_checksum(&self.valid_data)
+    }
+}
+
+impl From<ValidatedLines> for WriteBatch {
+    fn from(value: ValidatedLines) -> Self {
+        value.valid_data
+    }
+}
+
+/// A destination for the rows produced by the [`WriteValidator`]
+///
+/// Rows are routed to the sink by table and the gen1 chunk time they belong to. The default
+/// implementation is the [`WriteBatchSink`], which produces a [`WriteBatch`] for the WAL and
+/// buffer; other implementations can be used to reuse the validation with different buffer
+/// representations.
+pub trait RowSink {
+    /// The output produced by the sink once all rows have been pushed
+    type Output;
+
+    /// Push a row for the given table into the chunk starting at `chunk_time`
+    fn push(&mut self, table_id: TableId, chunk_time: i64, row: Row);
+
+    /// Consume the sink and produce its output
+    fn finish(self) -> Self::Output;
+}
+
+/// A [`RowSink`] that organizes rows into a [`WriteBatch`] of [`TableChunks`]
+#[derive(Debug)]
+pub struct WriteBatchSink {
+    database_id: DbId,
+    database_name: Arc<str>,
+    table_chunks: IndexMap<TableId, TableChunks>,
+}
+
+impl WriteBatchSink {
+    pub fn new(database_id: DbId, database_name: Arc<str>) -> Self {
+        Self {
+            database_id,
+            database_name,
+            table_chunks: IndexMap::new(),
+        }
+    }
+}
+
+impl RowSink for WriteBatchSink {
+    type Output = WriteBatch;
+
+    fn push(&mut self, table_id: TableId, chunk_time: i64, row: Row) {
+        let table_chunks = self.table_chunks.entry(table_id).or_default();
+        table_chunks.push_row(chunk_time, row);
+    }
+
+    fn finish(self) -> Self::Output {
+        WriteBatch::new(self.database_id, self.database_name, self.table_chunks)
+    }
+}
+
+/// A [`RowSink`] that produces a separate [`WriteBatch`] for each gen1 chunk, so that chunks
+/// can be flushed independently
+#[derive(Debug)]
+pub struct ChunkBatchSink {
+    database_id: DbId,
+    database_name: Arc<str>,
+    chunks: BTreeMap<i64, IndexMap<TableId, TableChunks>>,
+}
+
+impl ChunkBatchSink {
+    pub fn new(database_id: DbId, database_name: Arc<str>) -> Self {
+        Self {
+            database_id,
+            database_name,
+            chunks: BTreeMap::new(),
+        }
+    }
+}
+
+impl RowSink for ChunkBatchSink {
+    /// The start time of each chunk, with a batch of the chunk's rows, ordered by chunk time
+    type Output = Vec<(i64, WriteBatch)>;
+
+    fn push(&mut self, table_id: TableId, chunk_time: i64, row: Row) {
+        let table_chunks = self
+            .chunks
+            .entry(chunk_time)
+            .or_default()
+            .entry(table_id)
+            .or_default();
+        table_chunks.push_row(chunk_time, row);
+    }
+
+    fn finish(self) -> Self::Output {
+        self.chunks
+            .into_iter()
+            .map(|(chunk_time, table_chunks)| {
+                (
+                    chunk_time,
+                    WriteBatch::new(
+                        self.database_id,
+                        Arc::clone(&self.database_name),
+                        table_chunks,
+                    ),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Rows grouped by table, then by gen1 chunk time, then by encoded series key, as produced by
+/// the [`SeriesKeySink`]
+pub type SeriesGroupedRows = IndexMap<TableId, BTreeMap<i64, BTreeMap<Vec<u8>, Vec<Row>>>>;
+
+/// A [`RowSink`] that groups the rows of each table chunk by their series key
+///
+/// Within a chunk, series are ordered by their key as encoded by [`encode_series_key`], and the
+/// rows of a series are in the order they were written. Keeping the rows of a series together
+/// lets a columnar buffer compress them better, at the cost of encoding the series key of every
+/// row.
+#[derive(Debug)]
+pub struct SeriesKeySink {
+    db_schema: Arc<DatabaseSchema>,
+    tables: SeriesGroupedRows,
+}
+
+impl SeriesKeySink {
+    /// Create a sink for rows of tables in the given schema
+    pub fn new(db_schema: Arc<DatabaseSchema>) -> Self {
+        Self {
+            db_schema,
+            tables: IndexMap::new(),
+        }
+    }
+}
+
+impl RowSink for SeriesKeySink {
+    type Output = SeriesGroupedRows;
+
+    fn push(&mut self, table_id: TableId, chunk_time: i64, row: Row) {
+        let series_key = self
+            .db_schema
+            .table_definition_by_id(&table_id)
+            .map(|table_def| encode_series_key(&table_def, &row.fields))
+            .unwrap_or_default();
+        self.tables
+            .entry(table_id)
+            .or_default()
+            .entry(chunk_time)
+            .or_default()
+            .entry(series_key)
+            .or_default()
+            .push(row);
+    }
+
+    fn finish(self) -> Self::Output {
+        self.tables
+    }
+}
+
+impl WriteValidator<LinesParsed> {
+    /// Convert this into the inner [`LinesParsed`]
+    ///
+    /// This is mainly used for testing
+    pub fn into_inner(self) -> LinesParsed {
+        self.state
+    }
+
+    /// Convert a set of valid parsed lines to a [`ValidatedLines`] which will
+    /// be buffered and written to the WAL, if configured.
+    ///
+    /// This involves splitting out the writes into different batches for each chunk, which will
+    /// map to the `Gen1Duration`. This function should be infallible, because
+    /// the schema for incoming writes has been fully validated.
+    pub fn convert_lines_to_buffer(self, gen1_duration: Gen1Duration) -> ValidatedLines {
+        let sink = WriteBatchSink::new(
+            self.state.validated_schema.id,
+            Arc::clone(&self.state.validated_schema.name),
+        );
+        self.convert_lines_to_sink(gen1_duration, sink)
+    }
+
+    /// Convert a set of valid parsed lines to a [`ValidatedLines`] with a separate
+    /// [`WriteBatch`] for each gen1 chunk, ordered by chunk time
+    ///
+    /// This allows the chunks of a large write to be persisted concurrently. Like
+    /// [`convert_lines_to_buffer`][Self::convert_lines_to_buffer], this is infallible.
+    pub fn convert_lines_to_chunk_batches(
+        self,
+        gen1_duration: Gen1Duration,
+    ) -> ValidatedLines<Vec<(i64, WriteBatch)>> {
+        let sink = ChunkBatchSink::new(
+            self.state.validated_schema.id,
+            Arc::clone(&self.state.validated_schema.name),
+        );
+        self.convert_lines_to_sink(gen1_duration, sink)
+    }
+
+    /// Convert a set of valid parsed lines to a [`ValidatedLines`] with the rows of each table
+    /// chunk grouped by series key, using the [`SeriesKeySink`]
+    ///
+    /// This is opt-in, since it encodes the series key of every row. Like
+    /// [`convert_lines_to_buffer`][Self::convert_lines_to_buffer], this is infallible.
+    pub fn convert_lines_to_series_groups(
+        self,
+        gen1_duration: Gen1Duration,
+    ) -> ValidatedLines<SeriesGroupedRows> {
+        let sink = SeriesKeySink::new(Arc::clone(&self.state.validated_schema));
+        self.convert_lines_to_sink(gen1_duration, sink)
+    }
+
+    /// Convert a set of valid parsed lines into the given [`RowSink`]
+    ///
+    /// Each row is pushed to the sink along with the start time of the gen1 chunk it belongs to,
+    /// based on the `Gen1Duration`. Like [`convert_lines_to_buffer`][Self::convert_lines_to_buffer],
+    /// this is infallible.
+    pub fn convert_lines_to_sink<S: RowSink>(
+        self,
+        gen1_duration: Gen1Duration,
+        mut sink: S,
+    ) -> ValidatedLines<S::Output> {
+        let start = self.state.phase_durations.is_some().then(Instant::now);
+        let line_count = self.state.lines.len();
+        let mut field_count = 0;
+        let mut index_count = 0;
+        let mut series_keys = Vec::new();
+        let mut row_hashes = Vec::new();
+        let mut defaulted_timestamp_count = 0;
+        let mut new_table_line_count = 0;
+        let mut tables_touched = IndexSet::new();
+        let mut time_order = TimeOrderTracker::default();
+        let mut original_lines = Vec::new();
+        let mut effective_precisions = Vec::new();
+
+        for mut line in self.state.lines.into_iter() {
+            field_count += line.field_count;
+            index_count += line.index_count;
+            if line.timestamp_was_defaulted {
+                defaulted_timestamp_count += 1;
+            }
+            if line.table_created {
+                new_table_line_count += 1;
+            }
+            tables_touched.insert(line.table_id);
+            if let Some(series_key) = line.series_key_bytes.take() {
+                series_keys.push((line.table_id, series_key));
+            }
+            row_hashes.extend(line.row_hash);
+            if let Some(original_line) = line.original_line.take() {
+                original_lines.push((line.table_id, original_line));
+            }
+            effective_precisions.extend(line.effective_precision);
+
+            convert_qualified_line(line, &mut sink, gen1_duration, &mut time_order);
+        }
+        let valid_data = sink.finish();
+        let phase_durations = self
+            .state
+            .phase_durations
+            .zip(start)
+            .map(|(durations, start)| PhaseDurations {
+                chunking: start.elapsed(),
+                ..durations
+            });
+
+        ValidatedLines {
+            line_count,
+            valid_bytes_count: self.state.bytes,
+            field_count,
+            index_count,
+            errors: self.state.errors,
+            parse_error_count: self.state.parse_error_count,
+            warnings: self.state.warnings,
+            dropped_incompatible_count: self.state.dropped_incompatible_count,
+            lines_truncated: self.state.lines_truncated,
+            per_column_bytes: self.state.per_column_bytes,
+            series_keys,
+            row_hashes,
+            defaulted_timestamp_count,
+            new_table_line_count,
+            tables_touched: tables_touched.into_iter().collect(),
+            out_of_order_row_count: time_order.out_of_order_row_count,
+            original_lines,
+            effective_precisions,
+            validated_schema: self.state.validated_schema,
+            phase_durations,
+            valid_data,
+            catalog_updates: self.state.catalog_batch,
+        }
+    }
+}
+
+/// Tracks the latest timestamp written to each table, to find rows that arrive out of order
+#[derive(Debug, Default)]
+struct TimeOrderTracker {
+    max_time: HashMap<TableId, i64>,
+    out_of_order_row_count: HashMap<TableId, usize>,
+}
+
+fn convert_qualified_line<S: RowSink>(
+    line: QualifiedLine,
+    sink: &mut S,
+    gen1_duration: Gen1Duration,
+    time_order: &mut TimeOrderTracker,
+) {
+    // A row is out of order if an earlier row for the table has a later timestamp:
+    let max_time = time_order
+        .max_time
+        .entry(line.table_id)
+        .or_insert(line.row.time);
+    if line.row.time < *max_time {
+        *time_order
+            .out_of_order_row_count
+            .entry(line.table_id)
+            .or_default() += 1;
+    } else {
+        *max_time = line.row.time;
+    }
+
+    // Add the row into the correct chunk in the table
+    let chunk_time = gen1_duration.chunk_time_for_timestamp(Timestamp::new(line.row.time));
+    sink.push(line.table_id, chunk_time, line.row);
+}
+
+#[derive(Debug)]
+struct QualifiedLine {
+    table_id: TableId,
+    row: Row,
+    index_count: usize,
+    field_count: usize,
+    /// The encoded series key of the line, if enabled
+    series_key_bytes: Option<Vec<u8>>,
+    /// The hash of the row, if enabled
+    row_hash: Option<u64>,
+    /// Whether the line had no timestamp, so was given the ingest time
+    timestamp_was_defaulted: bool,
+    /// Whether the line created its table, rather than being written to an existing one
+    table_created: bool,
+    /// The original text of the line, if enabled
+    original_line: Option<String>,
+    /// The line number and the precision applied to the timestamp of the line, if enabled and
+    /// the line has a timestamp
+    effective_precision: Option<(usize, Precision)>,
+}
+
+/// Split line protocol into its lines, along with the zero-based index of the line in the
+/// input that each starts on, skipping blank lines and `#` comments
+///
+/// As in the line protocol parser, a newline between double quotes, e.g., in a string field
+/// value, does not end a line, so a line can span more than one line of the input. Keeping
+/// the index of each line here, rather than counting the lines produced by the parser, keeps
+/// line numbers accurate to the original input.
+fn split_lines(lp: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut rest = lp;
+    let mut line_idx = 0;
+    std::iter::from_fn(move || {
+        while !rest.is_empty() {
+            let mut in_quotes = false;
+            let mut escaped = false;
+            let end = rest.char_indices().find_map(|(i, c)| {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_quotes = !in_quotes,
+                    '\n' if !in_quotes => return Some(i),
+                    _ => (),
+                }
+                None
+            });
+            let line = match end {
+                Some(i) => {
+                    let line = &rest[..i];
+                    rest = &rest[i + 1..];
+                    line
+                }
+                None => std::mem::take(&mut rest),
+            };
+            let start_idx = line_idx;
+            line_idx += line.matches('\n').count() + 1;
+
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let trimmed = line.trim_start();
+            if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                return Some((start_idx, line));
+            }
+        }
+        None
+    })
+}
+
+/// Split line protocol into sub-batches of at most `max_lines_per_batch` lines, each of which
+/// can be validated on its own, e.g., to stream a large write through a reused
+/// [`WriteValidator`] in bounded pieces
+///
+/// Lines are never split, and the sub-batches are contiguous slices of the input, so
+/// concatenating them gives back the input exactly. As when validating, a line with a newline
+/// in a quoted string value counts as one line, however long it is, and blank lines and
+/// comments do not count towards the limit. Line numbers in the errors for a sub-batch are
+/// relative to the start of that sub-batch.
+///
+/// # Panics
+///
+/// If `max_lines_per_batch` is zero.
+pub fn split_lp(lp: &str, max_lines_per_batch: usize) -> impl Iterator<Item = &str> {
+    assert!(
+        max_lines_per_batch > 0,
+        "sub-batches must have room for at least one line"
+    );
+    // each line is a slice of the input, so its offset in the input is where it starts:
+    let mut boundaries = split_lines(lp)
+        .map(move |(_, line)| line.as_ptr() as usize - lp.as_ptr() as usize)
+        .step_by(max_lines_per_batch)
+        .skip(1);
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        if start == lp.len() {
+            return None;
+        }
+        let end = boundaries.next().unwrap_or(lp.len());
+        let batch = &lp[start..end];
+        start = end;
+        Some(batch)
+    })
+}
+
+/// Resolve [`Precision::AutoBatch`] to a single precision for all lines of the write
+fn resolve_batch_precision(lp: &str, precision: Precision) -> Precision {
+    match precision {
+        Precision::AutoBatch => guess_batch_precision(lp),
+        precision => precision,
+    }
+}
+
+/// Guess a single precision for all timestamps in the line protocol, using a majority vote
+/// on the precision guessed for each timestamp
+///
+/// Ties are broken in favour of the precision guessed for the first timestamp. This needs to
+/// parse the line protocol up front, so it is only done for [`Precision::AutoBatch`].
+fn guess_batch_precision(lp: &str) -> Precision {
+    const CANDIDATES: [Precision; 4] = [
+        Precision::Second,
+        Precision::Millisecond,
+        Precision::Microsecond,
+        Precision::Nanosecond,
+    ];
+    let mut votes = [0usize; CANDIDATES.len()];
+    let mut first = None;
+    for ts in parse_lines(lp).filter_map(|l| l.ok().and_then(|l| l.timestamp)) {
+        let guess = crate::guess_precision(ts);
+        let idx = CANDIDATES
+            .iter()
+            .position(|p| *p == guess)
+            .expect("guessed precision is one of the candidates");
+        votes[idx] += 1;
+        first.get_or_insert(idx);
+    }
+    let Some(first) = first else {
+        // there are no timestamps, so the precision will not be used:
+        return Precision::Nanosecond;
+    };
+    let max_votes = votes.iter().copied().max().unwrap_or_default();
+    if votes[first] == max_votes {
+        CANDIDATES[first]
+    } else {
+        let idx = votes
+            .iter()
+            .position(|v| *v == max_votes)
+            .expect("max votes is one of the votes");
+        CANDIDATES[idx]
+    }
+}
+
+/// Apply the precision to a timestamp, returning the timestamp in nanoseconds, or `None` if it
+/// overflows, along with the precision that was applied, which for [`Precision::Auto`] is
+/// guessed from the timestamp
+fn apply_precision_to_timestamp(precision: Precision, ts: i64) -> (Option<i64>, Precision) {
+    let precision = resolve_precision(precision, ts);
+    (
+        ts.checked_mul(precision_multiplier(precision, ts)),
+        precision,
+    )
+}
+
+/// The multiplier to convert a timestamp in the given precision to nanoseconds
+fn precision_multiplier(precision: Precision, ts: i64) -> i64 {
+    match resolve_precision(precision, ts) {
+        Precision::Second => 1_000_000_000,
+        Precision::Millisecond => 1_000_000,
+        Precision::Microsecond => 1_000,
+        Precision::Nanosecond => 1,
+
+        Precision::Auto | Precision::AutoBatch => unreachable!(),
+    }
+}
+
+/// Resolve the precision to apply to a timestamp, guessing it from the timestamp for
+/// [`Precision::Auto`]
+fn resolve_precision(precision: Precision, ts: i64) -> Precision {
+    match precision {
+        // AutoBatch is resolved before lines are validated, but if it is not, fall back
+        // to guessing the precision per line:
+        Precision::Auto | Precision::AutoBatch => crate::guess_precision(ts),
+        precision => precision,
+    }
+}
+
+#[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{borrow::Cow, sync::Arc, time::Duration};
+
+    use super::{
+        BatchState, ClockSkewPolicy, EmptyRowPolicy, FloatToIntPolicy, IncompatibleLineMode,
+        LinesParsed, MaxLinesPolicy, MeasurementCasePolicy, MeasurementNamePolicy, RowSink,
+        Scratch, SeriesKeyMissingPolicy, StringLengthPolicy, TimestampSpanPolicy, ValidatorConfig,
+        WithCatalog, WriteValidator, encode_series_key,
+    };
+    use crate::{Precision, WriteLineError, write_buffer::Error};
+
+    use arrow_util::assert_batches_sorted_eq;
+    use data_types::NamespaceName;
+    use hashbrown::HashMap;
+    use influxdb3_catalog::catalog::Catalog;
+    use influxdb3_id::{ColumnId, TableId};
+    use influxdb3_wal::{Field, FieldData, Gen1Duration, Row, create};
+    use iox_time::Time;
+    use schema::{InfluxColumnType, InfluxFieldType, TIME_COLUMN_NAME};
+
+    /// A catalog with no databases, for tests to validate writes against
+    fn test_catalog() -> Arc<Catalog> {
+        Arc::new(Catalog::new(
+            "sample-host-id".into(),
+            "sample-instance-id".into(),
+        ))
+    }
+
+    /// Validate a write of the line protocol to the `test` database of the catalog, with the
+    /// validator configured by `configure`
+    ///
+    /// The write is ingested at time zero, and accepts partial writes, so invalid lines are
+    /// reported in the errors of the validated lines rather than failing the write.
+    fn validate_lp(
+        catalog: &Arc<Catalog>,
+        configure: impl FnOnce(WriteValidator<WithCatalog>) -> WriteValidator<WithCatalog>,
+        lp: &str,
+        precision: Precision,
+    ) -> Result<WriteValidator<LinesParsed>, Error> {
+        let namespace = NamespaceName::new("test").unwrap();
+        let validator = WriteValidator::initialize(namespace, Arc::clone(catalog), 0)?;
+        configure(validator).parse_lines_and_update_schema(
+            lp,
+            true,
+            Time::from_timestamp_nanos(0),
+            precision,
+        )
+    }
+
+    #[test]
+    fn write_validator() -> Result<(), Error> {
+        let namespace = NamespaceName::new("test").unwrap();
+        let catalog = test_catalog();
+        // partial writes are not accepted, so any invalid line fails the write:
+        let validate = |lp: &str| {
+            WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog), 0)
+                .unwrap()
+                .parse_lines_and_update_schema(
+                    lp,
+                    false,
+                    Time::from_timestamp_nanos(0),
+                    Precision::Auto,
+                )
+                .unwrap()
+                .convert_lines_to_buffer(Gen1Duration::new_5m())
+        };
+        let result = validate("cpu,tag1=foo val1=\"bar\" 1234");
+
+        assert_eq!(result.line_count, 1);
+        assert_eq!(result.field_count, 1);
+        assert_eq!(result.index_count, 1);
+        assert!(result.errors.is_empty());
+
+        assert_eq!(result.valid_data.database_name.as_ref(), namespace.as_str());
+        // cpu table
+        let batch = result
+            .valid_data
+            .table_chunks
+            .get(&TableId::from(0))
+            .unwrap();
+        assert_eq!(batch.row_count(), 1);
+
+        // Validate another write, the result should be very similar, but now the catalog
+        // has the table/columns added, so it will excercise a different code path:
+        let result = validate("cpu,tag1=foo val1=\"bar\" 1235");
+
+        println!("result: {result:?}");
+        assert_eq!(result.line_count, 1);
+        assert_eq!(result.field_count, 1);
+        assert_eq!(result.index_count, 1);
+        assert!(result.errors.is_empty());
+
+        // Validate another write, this time adding a new field:
+        let result = validate("cpu,tag1=foo val1=\"bar\",val2=false 1236");
+
+        println!("result: {result:?}");
+        assert_eq!(result.line_count, 1);
+        assert_eq!(result.field_count, 2);
+        assert_eq!(result.index_count, 1);
+        assert!(result.errors.is_empty());
+
+        Ok(())
+    }
+
+    /// A sink that only counts the rows pushed per table and chunk
+    #[derive(Debug, Default)]
+    struct CountingSink {
+        counts: HashMap<(TableId, i64), usize>,
+    }
+
+    impl RowSink for CountingSink {
+        type Output = HashMap<(TableId, i64), usize>;
+
+        fn push(&mut self, table_id: TableId, chunk_time: i64, _row: Row) {
+            *self.counts.entry((table_id, chunk_time)).or_default() += 1;
+        }
+
+        fn finish(self) -> Self::Output {
+            self.counts
+        }
+    }
+
+    #[test]
+    fn write_validator_custom_sink() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,tag1=foo val1=1 1\n\
+            cpu,tag1=bar val1=2 2\n\
+            cpu,tag1=foo val1=3 600000000000",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_sink(Gen1Duration::new_5m(), CountingSink::default());
+
+        assert_eq!(result.line_count, 3);
+        assert_eq!(result.field_count, 3);
+        assert_eq!(result.index_count, 3);
+        assert!(result.errors.is_empty());
+        let table_id = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_name_to_id("cpu")
+            .unwrap();
+        assert_eq!(result.valid_data.len(), 2);
+        assert_eq!(result.valid_data.get(&(table_id, 0)), Some(&2));
+        assert_eq!(
+            result.valid_data.get(&(table_id, 600_000_000_000)),
+            Some(&1)
+        );
+    }
+
+    #[test]
+    fn future_timestamp_skew() {
+        let catalog = test_catalog();
+        // the lines are ingested at time zero:
+        let lp = "cpu val=1 0\n\
+            cpu val=2 30000000000\n\
+            cpu val=3 90000000000\n\
+            cpu val=4";
+
+        // by default, skewed lines are accepted with a warning:
+        let result = validate_lp(
+            &catalog,
+            |validator| {
+                validator.with_max_future_skew(Duration::from_secs(60), ClockSkewPolicy::Warn)
+            },
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 4);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].line_number, 3);
+        assert!(result.warnings[0].warning_message.contains("90s ahead"));
+
+        // lines can be rejected instead:
+        let result = validate_lp(
+            &catalog,
+            |validator| {
+                validator.with_max_future_skew(Duration::from_secs(60), ClockSkewPolicy::Reject)
+            },
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 3);
+        assert!(result.warnings.is_empty());
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].line_number, 3);
+    }
+
+    #[test]
+    fn rejected_line_leaves_no_timestamp_warnings() {
+        let catalog = test_catalog();
+        // the skewed line is rejected by the row validator, after its timestamp is checked:
+        let result = validate_lp(
+            &catalog,
+            |validator| {
+                validator
+                    .with_max_future_skew(Duration::from_secs(60), ClockSkewPolicy::Warn)
+                    .with_max_timestamp_span(Duration::from_secs(60), TimestampSpanPolicy::Warn)
+                    .with_row_validator(|row, _| match row.time {
+                        90000000000 => Err("rejected".to_string()),
+                        _ => Ok(()),
+                    })
+            },
+            "cpu val=1 0\n\
+            cpu val=2 90000000000\n\
+            cpu val=3 30000000000",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 2);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].line_number, 2);
+        // neither the skew of the rejected line nor the span it would have made are reported:
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn per_column_bytes() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_column_size_accounting(),
+            "cpu,host=abc msg=\"hello\",val=1i,ok=true 1\n\
+            cpu,host=de msg=\"hi\",val=2i 2",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+
+        let table = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        let bytes = |name: &str| {
+            result
+                .per_column_bytes
+                .get(&table.column_name_to_id(name).unwrap())
+                .copied()
+        };
+        assert_eq!(bytes("host"), Some(5));
+        assert_eq!(bytes("msg"), Some(7));
+        assert_eq!(bytes("val"), Some(16));
+        assert_eq!(bytes("ok"), Some(1));
+        assert_eq!(bytes("time"), None);
+    }
+
+    #[test]
+    fn auto_batch_precision() {
+        let lp = "cpu val=1 1708976567000\n\
+            cpu val=2 1708976568000\n\
+            cpu val=3 1000";
+        let rows = |precision| {
+            validate_lp(&test_catalog(), |validator| validator, lp, precision)
+                .unwrap()
+                .into_inner()
+                .to_rows()
+                .into_iter()
+                .map(|row| row.time)
+                .collect::<Vec<_>>()
+        };
+
+        // guessing per line puts the last line in seconds:
+        assert_eq!(
+            rows(Precision::Auto),
+            [
+                1_708_976_567_000_000_000,
+                1_708_976_568_000_000_000,
+                1_000_000_000_000
+            ]
+        );
+        // guessing for the batch puts all lines in milliseconds:
+        assert_eq!(
+            rows(Precision::AutoBatch),
+            [
+                1_708_976_567_000_000_000,
+                1_708_976_568_000_000_000,
+                1_000_000_000
+            ]
+        );
+    }
+
+    #[test]
+    fn into_wal_rows() {
+        let catalog = test_catalog();
+        let (table_rows, catalog_batch) = validate_lp(
+            &catalog,
+            |validator| validator,
+            "mem val=1 3\n\
+            cpu val=1 1\n\
+            mem val=2 600000000000",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .into_inner()
+        .into_wal_rows();
+
+        let db = catalog.db_schema("test").unwrap();
+        let mem_id = db.table_name_to_id("mem").unwrap();
+        let cpu_id = db.table_name_to_id("cpu").unwrap();
+        assert_eq!(
+            table_rows.keys().copied().collect::<Vec<_>>(),
+            [mem_id, cpu_id]
+        );
+        assert_eq!(
+            table_rows[&mem_id]
+                .iter()
+                .map(|r| r.time)
+                .collect::<Vec<_>>(),
+            [3, 600_000_000_000]
+        );
+        assert_eq!(table_rows[&cpu_id].len(), 1);
+        assert_eq!(catalog_batch.unwrap().batch().ops.len(), 2);
+    }
+
+    #[test]
+    fn measurement_name_policy() {
+        let catalog = test_catalog();
+        let lp = "cpu\\,a/b val=1 1\n\
+            mem val=1 1";
+
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_measurement_name_policy(MeasurementNamePolicy::Reject),
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 1);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].line_number, 1);
+        assert!(
+            result.errors[0]
+                .error_message
+                .contains("invalid measurement name 'cpu,a/b'")
+        );
+
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_measurement_name_policy(MeasurementNamePolicy::Sanitize),
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 2);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].line_number, 1);
+        let db = catalog.db_schema("test").unwrap();
+        assert!(db.table_definition("cpu_a_b").is_some());
+        assert!(db.table_definition("cpu,a/b").is_none());
+
+        // a line that is rejected after its measurement name is sanitized has no warning:
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_measurement_name_policy(MeasurementNamePolicy::Sanitize),
+            "cpu\\,a/b val=\"one\" 2",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn max_batch_bytes() {
+        let catalog = test_catalog();
+        let lp = "cpu val=1 1\ncpu val=2 2";
+
+        let err = validate_lp(
+            &catalog,
+            |validator| validator.with_max_batch_bytes(lp.len() - 1),
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::BatchTooLarge { size, limit } if size == lp.len() && limit == lp.len() - 1
+        ));
+        // nothing was parsed, so the catalog was not updated:
+        assert_eq!(catalog.db_schema("test").unwrap().table_count(), 0);
+
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_max_batch_bytes(lp.len()),
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 2);
+    }
+
+    #[test]
+    fn series_keys() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_series_keys(),
+            "cpu,region=us,host=a val=1 1\n\
+            cpu,host=a,region=us val=2 2\n\
+            cpu,host=b val=3 3",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+
+        let table = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        assert_eq!(result.series_keys.len(), 3);
+        assert!(
+            result
+                .series_keys
+                .iter()
+                .all(|(id, _)| *id == table.table_id)
+        );
+        let encoded = |parts: &[&str]| {
+            let mut expected = Vec::new();
+            for part in parts {
+                expected.extend_from_slice(&(part.len() as u32).to_le_bytes());
+                expected.extend_from_slice(part.as_bytes());
+            }
+            expected
+        };
+        // the tags of a v1 table are encoded sorted by name, regardless of the order they
+        // were written in:
+        assert_eq!(result.series_keys[0].1, result.series_keys[1].1);
+        assert_eq!(
+            result.series_keys[0].1,
+            encoded(&["host", "a", "region", "us"])
+        );
+        // rows missing a tag omit it from the key:
+        let row = &result.valid_data.table_chunks[&table.table_id].chunk_time_to_chunk[&0].rows[2];
+        assert_eq!(
+            result.series_keys[2].1,
+            encode_series_key(&table, &row.fields)
+        );
+        assert_eq!(result.series_keys[2].1.len(), 4 + 4 + 4 + 1);
+
+        // the tags of a v3 table are encoded in the order of its series key:
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_series_keys().with_explicit_series_keys(),
+            "mem,region=us,host=a free=1i 1",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(
+            result.series_keys[0].1,
+            encoded(&["region", "us", "host", "a"])
+        );
+    }
+
+    #[test]
+    fn injected_tags() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| {
+                validator.with_injected_tags(vec![("_ingest_node".into(), "node-a".into())])
+            },
+            "cpu,host=a val=1 1\n\
+            cpu,host=b,_ingest_node=spoofed val=2 2\n\
+            mem val=3 3",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+
+        // injected tags don't count towards the index count:
+        assert_eq!(result.index_count, 2);
+        let db = catalog.db_schema("test").unwrap();
+        for table_name in ["cpu", "mem"] {
+            let table = db.table_definition(table_name).unwrap();
+            let col_id = table.column_name_to_id("_ingest_node").unwrap();
+            let rows =
+                &result.valid_data.table_chunks[&table.table_id].chunk_time_to_chunk[&0].rows;
+            for row in rows {
+                let values = row
+                    .fields
+                    .iter()
+                    .filter(|f| f.id == col_id)
+                    .map(|f| f.value.clone())
+                    .collect::<Vec<_>>();
+                assert_eq!(values, [FieldData::Tag("node-a".to_string())]);
+            }
+        }
+    }
+
+    #[test]
+    fn max_catalog_ops() {
+        let catalog = test_catalog();
+        let lp = "cpu val=1 1\n\
+            mem val=1 1\n\
+            cpu val=1,new=2 2";
+
+        let err = validate_lp(
+            &catalog,
+            |validator| validator.with_max_catalog_ops(2),
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::TooManyCatalogOps { count: 3, limit: 2 }
+        ));
+        assert_eq!(catalog.db_schema("test").unwrap().table_count(), 0);
+
+        validate_lp(
+            &catalog,
+            |validator| validator.with_max_catalog_ops(3),
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap();
+        assert_eq!(catalog.db_schema("test").unwrap().table_count(), 2);
+    }
+
+    #[test]
+    fn to_record_batches() {
+        let catalog = test_catalog();
+        let batches = validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5 1\n\
+            cpu,host=b idle=10i 2\n\
+            cpu usage=0.7,idle=5i 3",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .into_inner()
+        .to_record_batches()
+        .unwrap();
+
+        let table_id = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_name_to_id("cpu")
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_batches_sorted_eq!(
+            [
+                "+------+------+--------------------------------+-------+",
+                "| host | idle | time                           | usage |",
+                "+------+------+--------------------------------+-------+",
+                "|      | 5    | 1970-01-01T00:00:00.000000003Z | 0.7   |",
+                "| a    |      | 1970-01-01T00:00:00.000000001Z | 0.5   |",
+                "| b    | 10   | 1970-01-01T00:00:00.000000002Z |       |",
+                "+------+------+--------------------------------+-------+",
+            ],
+            &[batches[&table_id].clone()]
+        );
+    }
+
+    #[test]
+    fn row_hashes() {
+        let catalog = test_catalog();
+        let hashes = |lp: &str| {
+            validate_lp(
+                &catalog,
+                |validator| validator.with_row_hashes(),
+                lp,
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m())
+            .row_hashes
+        };
+
+        let first = hashes(
+            "cpu,host=a,region=us usage=0.5,idle=1i 1\n\
+            cpu,host=a,region=us usage=0.5,idle=1i 2\n\
+            cpu,host=b,region=us usage=0.5,idle=1i 1\n\
+            cpu,host=a,region=us usage=0.6,idle=1i 1",
+        );
+        assert_eq!(first.len(), 4);
+        // rows that differ in timestamp, series key, or field values hash differently:
+        for i in 1..4 {
+            assert_ne!(first[0], first[i]);
+        }
+        // a retried write produces the same hashes, even with tags and fields re-ordered:
+        let retried = hashes("cpu,region=us,host=a idle=1i,usage=0.5 1");
+        assert_eq!(retried, [first[0]]);
+    }
+
+    #[test]
+    fn integral_floats_as_integers() {
+        let catalog = test_catalog();
+        let validate = |lp: &str, coerce: bool| {
+            let configure = |validator: WriteValidator<WithCatalog>| {
+                if coerce {
+                    validator.with_integral_floats_as_integers()
+                } else {
+                    validator
+                }
+            };
+            validate_lp(&catalog, configure, lp, Precision::Nanosecond)
+                .unwrap()
+                .convert_lines_to_buffer(Gen1Duration::new_5m())
+        };
+        validate("cpu val=1i 1", false);
+
+        // by default, floats are rejected for integer columns:
+        let result = validate("cpu val=1e3 2", false);
+        assert_eq!(result.errors.len(), 1);
+
+        let result = validate(
+            "cpu val=1e3 2\n\
+            cpu val=42.0 3\n\
+            cpu val=1.5 4\n\
+            cpu val=1e300 5",
+            true,
+        );
+        assert_eq!(result.line_count, 2);
+        assert_eq!(result.errors.len(), 2);
+        assert_eq!(result.errors[0].line_number, 3);
+        assert!(
+            result.errors[0]
+                .error_message
+                .contains("cannot be converted to an integer")
+        );
+        assert_eq!(result.errors[1].line_number, 4);
+        let table = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        let col_id = table.column_name_to_id("val").unwrap();
+        let values = result.valid_data.table_chunks[&table.table_id].chunk_time_to_chunk[&0]
+            .rows
+            .iter()
+            .flat_map(|r| r.fields.iter().filter(|f| f.id == col_id))
+            .map(|f| f.value.clone())
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            values.as_slice(),
+            [FieldData::Integer(1000), FieldData::Integer(42)]
+        ));
+    }
+
+    #[test]
+    fn check_lines() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5 1",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        let sequence = catalog.sequence_number();
+
+        let checked = WriteValidator::initialize(namespace, Arc::clone(&catalog), 0)
+            .unwrap()
+            .check_lines(
+                "cpu,host=a usage=0.7 2\n\
+                cpu,host=b usage=\"high\" 3\n\
+                mem,host=a free=1i 4\n\
+                mem,host=a free=1.5 5\n\
+                not line protocol",
+                Time::from_timestamp_nanos(0),
+                Precision::Nanosecond,
+            )
+            .unwrap();
+        assert_eq!(checked.line_count, 2);
+        assert_eq!(checked.index_count, 2);
+        assert_eq!(checked.field_count, 2);
+        assert_eq!(
+            checked
+                .errors
+                .iter()
+                .map(|e| e.line_number)
+                .collect::<Vec<_>>(),
+            [2, 4, 5]
+        );
+        assert!(checked.warnings.is_empty());
+
+        // checking does not change the catalog:
+        assert_eq!(catalog.sequence_number(), sequence);
+        assert!(
+            catalog
+                .db_schema("test")
+                .unwrap()
+                .table_definition("mem")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn defaulted_timestamp_count() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        let result = WriteValidator::initialize(namespace, Arc::clone(&catalog), 0)
+            .unwrap()
+            .parse_lines_and_update_schema(
+                "cpu,host=a usage=0.5 1\n\
+                cpu,host=b usage=0.7\n\
+                cpu,host=c usage=0.9",
+                false,
+                Time::from_timestamp_nanos(42),
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            result
+                .lines
+                .iter()
+                .map(|l| (l.row.time, l.timestamp_was_defaulted))
+                .collect::<Vec<_>>(),
+            [(1, false), (42, true), (42, true)]
+        );
+        let result =
+            WriteValidator { state: result }.convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.defaulted_timestamp_count, 2);
+    }
+
+    #[test]
+    fn timestamp_deltas() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        let validate = |base_ns: i64, precision: Precision, lp: &str| {
+            WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog), 0)
+                .unwrap()
+                .with_timestamp_deltas(base_ns)
+                .parse_lines_and_update_schema(lp, true, Time::from_timestamp_nanos(7), precision)
+                .unwrap()
+                .convert_lines_to_buffer(Gen1Duration::new_5m())
+        };
+
+        let result = validate(
+            1_000_000_000,
+            Precision::Millisecond,
+            "cpu usage=0.5 0\n\
+            cpu usage=0.7 250\n\
+            cpu usage=0.9",
+        );
+        assert!(result.errors.is_empty());
+        let times = result
+            .valid_data
+            .table_chunks
+            .values()
+            .flat_map(|t| t.chunk_time_to_chunk.values())
+            .flat_map(|c| c.rows.iter().map(|r| r.time))
+            .collect::<Vec<_>>();
+        assert_eq!(times, [1_000_000_000, 1_250_000_000, 7]);
+
+        let result = validate(
+            i64::MAX - 10,
+            Precision::Nanosecond,
+            "cpu usage=0.5 10\n\
+            cpu usage=0.7 11\n\
+            cpu usage=0.9 -1",
+        );
+        assert_eq!(result.line_count, 1);
+        assert_eq!(result.errors.len(), 2);
+        assert_eq!(result.errors[0].line_number, 2);
+        assert!(result.errors[0].error_message.contains("overflows"));
+        assert_eq!(result.errors[1].line_number, 3);
+        assert!(
+            result.errors[1]
+                .error_message
+                .contains("negative timestamp delta")
+        );
+    }
+
+    #[test]
+    fn on_table_created() {
+        let catalog = test_catalog();
+        validate_lp(
+            &catalog,
+            |validator| validator,
+            "disk,host=a used=1i 1",
+            Precision::Nanosecond,
+        )
+        .unwrap();
+
+        let created = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let created_captured = Arc::clone(&created);
+        validate_lp(
+            &catalog,
+            |validator| {
+                validator.with_on_table_created(move |table_id, table_name| {
+                    created_captured
+                        .lock()
+                        .unwrap()
+                        .push((table_id, table_name.to_string()));
+                })
+            },
+            "cpu,host=a usage=0.5 1\n\
+            disk,host=a used=2i 2\n\
+            cpu,host=b usage=0.7 3\n\
+            mem,host=a free=1i 4",
+            Precision::Nanosecond,
+        )
+        .unwrap();
+
+        let db_schema = catalog.db_schema("test").unwrap();
+        let expected = ["cpu", "mem"]
+            .map(|name| (db_schema.table_name_to_id(name).unwrap(), name.to_string()));
+        assert_eq!(*created.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn original_lines() {
+        let catalog = test_catalog();
+        let lp = "cpu,host=a usage=0.5 1\n\
+            cpu,host=b usage=\"high\" 2\n\
+            mem,host=a free=1i 3";
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_original_lines(),
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        let db_schema = catalog.db_schema("test").unwrap();
+        assert_eq!(
+            result.original_lines,
+            [
+                (
+                    db_schema.table_name_to_id("cpu").unwrap(),
+                    "cpu,host=a usage=0.5 1".to_string()
+                ),
+                (
+                    db_schema.table_name_to_id("mem").unwrap(),
+                    "mem,host=a free=1i 3".to_string()
+                ),
+            ]
+        );
+
+        // original lines are not kept by default:
+        let result = validate_lp(&catalog, |validator| validator, lp, Precision::Nanosecond)
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert!(result.original_lines.is_empty());
+    }
+
+    #[test]
+    fn validate_multi_db() {
+        let catalog = test_catalog();
+        let foo = NamespaceName::new("foo").unwrap();
+        let bar = NamespaceName::new("bar").unwrap();
+        let baz = NamespaceName::new("baz").unwrap();
+        let results = super::validate_multi_db(
+            &catalog,
+            vec![
+                (foo.clone(), "cpu,host=a usage=0.5 1"),
+                (bar.clone(), "cpu,host=a usage=0.5 1\nnot line protocol"),
+                (baz.clone(), "mem,host=a free=1i 1"),
+                (foo.clone(), "cpu,host=b usage=0.7 2"),
+            ],
+            false,
+            Time::from_timestamp_nanos(0),
+            Precision::Nanosecond,
+            Gen1Duration::new_5m(),
+        );
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[&foo].len(), 2);
+        assert!(
+            results[&foo]
+                .iter()
+                .all(|result| result.as_ref().unwrap().line_count == 1)
+        );
+        assert_eq!(results[&baz][0].as_ref().unwrap().line_count, 1);
+        assert!(matches!(results[&bar][..], [Err(Error::ParseError(_))]));
+
+        // only the databases that were written successfully were updated:
+        assert!(
+            catalog
+                .db_schema("foo")
+                .unwrap()
+                .table_definition("cpu")
+                .is_some()
+        );
+        assert!(
+            catalog
+                .db_schema("baz")
+                .unwrap()
+                .table_definition("mem")
+                .is_some()
+        );
+        assert!(
+            catalog
+                .db_schema("bar")
+                .unwrap()
+                .table_definition("cpu")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn float_to_int_policy() {
+        let catalog = test_catalog();
+        let validate = |lp: &str, policy: FloatToIntPolicy| {
+            validate_lp(
+                &catalog,
+                |validator| validator.with_float_to_int_policy(policy),
+                lp,
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m())
+        };
+        let values = |result: &super::ValidatedLines| {
+            let table = catalog
+                .db_schema("test")
+                .unwrap()
+                .table_definition("cpu")
+                .unwrap();
+            let col_id = table.column_name_to_id("val").unwrap();
+            result.valid_data.table_chunks[&table.table_id].chunk_time_to_chunk[&0]
+                .rows
+                .iter()
+                .flat_map(|r| r.fields.iter().filter(|f| f.id == col_id))
+                .map(|f| match f.value {
+                    FieldData::Integer(v) => v,
+                    _ => panic!("expected an integer"),
+                })
+                .collect::<Vec<_>>()
+        };
+        validate("cpu val=1i 1", FloatToIntPolicy::Error);
+        let lp = "cpu val=2.7 2\n\
+            cpu val=-2.5 3\n\
+            cpu val=4.0 4\n\
+            cpu val=1e300 5";
+
+        let result = validate(lp, FloatToIntPolicy::Error);
+        assert_eq!(result.line_count, 0);
+        assert_eq!(result.errors.len(), 4);
+
+        let result = validate(lp, FloatToIntPolicy::Truncate);
+        assert_eq!(values(&result), [2, -2, 4]);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].line_number, 4);
+        assert_eq!(result.warnings.len(), 3);
+        assert_eq!(
+            result.warnings[0].warning_message,
+            "float value 2.7 for integer field 'val' on line 1 was converted to 2"
+        );
+
+        let result = validate(lp, FloatToIntPolicy::Round);
+        assert_eq!(values(&result), [3, -3, 4]);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.warnings.len(), 3);
+
+        // a line that is rejected after its value is converted has no warning:
+        let result = validate(
+            "cpu val=3.7,flag=true 6\n\
+            cpu val=4.7,flag=1i 7",
+            FloatToIntPolicy::Truncate,
+        );
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].line_number, 2);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].line_number, 1);
+    }
+
+    #[test]
+    fn reused_validator() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        let mut validator =
+            WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog), 0).unwrap();
+        let mut validate = |lp: &str| {
+            validator
+                .parse_lines_and_update_schema_reusing(
+                    lp,
+                    false,
+                    Time::from_timestamp_nanos(0),
+                    Precision::Nanosecond,
+                )
+                .map(|lines| lines.convert_lines_to_buffer(Gen1Duration::new_5m()))
+        };
+        let result = validate("cpu,host=a usage=0.5 1").unwrap();
+        assert!(result.catalog_updates.is_some());
+
+        // the table created by the previous write is seen by the reused validator:
+        let result = validate("cpu,host=b usage=0.7 2").unwrap();
+        assert!(result.catalog_updates.is_none());
+        assert!(validate("cpu,host=b usage=\"high\" 3").is_err());
+
+        // as are changes made to the catalog by another validator:
+        validate_lp(
+            &catalog,
+            |validator| validator,
+            "mem,host=a free=1i 1",
+            Precision::Nanosecond,
+        )
+        .unwrap();
+        let result = validate("mem,host=a free=2i 2").unwrap();
+        assert!(result.catalog_updates.is_none());
+        assert!(validate("mem,host=a free=2.5 3").is_err());
+    }
+
+    /// The line protocol parser normalizes every boolean spelling it accepts to a `bool`, so
+    /// booleans are stored canonically without any normalization in the validator. Numeric
+    /// values such as `0` and `1` are floats, not booleans, and other spellings, e.g., `yes`
+    /// or `tRuE`, are not valid field values.
+    #[test]
+    fn boolean_field_spellings() {
+        let catalog = test_catalog();
+        let spellings = [
+            ("t", true),
+            ("T", true),
+            ("true", true),
+            ("True", true),
+            ("TRUE", true),
+            ("f", false),
+            ("F", false),
+            ("false", false),
+            ("False", false),
+            ("FALSE", false),
+        ];
+        let lp = spellings
+            .iter()
+            .enumerate()
+            .map(|(i, (spelling, _))| format!("cpu flag={spelling} {i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let rows = validate_lp(&catalog, |validator| validator, &lp, Precision::Nanosecond)
+            .unwrap()
+            .into_inner()
+            .to_rows();
+        let table = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        let col_id = table.column_name_to_id("flag").unwrap();
+        let values = rows
+            .iter()
+            .flat_map(|r| r.fields.iter().filter(|f| f.id == col_id))
+            .map(|f| match f.value {
+                FieldData::Boolean(v) => v,
+                _ => panic!("expected a boolean"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, spellings.map(|(_, v)| v));
+
+        let result = validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu flag=1 10\n\
+            cpu flag=0 11\n\
+            cpu flag=yes 12\n\
+            cpu flag=tRuE 13",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 0);
+        assert_eq!(result.errors.len(), 4);
+        assert!(result.errors[0].error_message.contains(
+            "expected type iox::column_type::field::boolean, but got iox::column_type::field::float"
+        ));
+    }
+
+    #[test]
+    fn read_only_snapshot() {
+        let catalog = test_catalog();
+        validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5 1",
+            Precision::Nanosecond,
+        )
+        .unwrap();
+        let snapshot = catalog.db_schema("test").unwrap();
+        let sequence = catalog.sequence_number();
+        let lp = "cpu,host=b usage=0.7 2\n\
+            cpu,host=b,region=us usage=0.7 3\n\
+            cpu,host=b usage=0.7,idle=0.2 4\n\
+            mem,host=a free=1i 5\n\
+            mem,host=a free=2i 6";
+
+        let result = WriteValidator::initialize_read_only(Arc::clone(&snapshot))
+            .parse_lines_and_update_schema(
+                lp,
+                true,
+                Time::from_timestamp_nanos(0),
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 1);
+        assert!(result.catalog_updates.is_none());
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| e.line_number)
+                .collect::<Vec<_>>(),
+            [2, 3, 4, 5]
+        );
+        assert_eq!(
+            result.errors[2].error_message,
+            "line 4 would require a catalog change to create table 'mem', which is not allowed \
+            on a read-only schema snapshot"
+        );
+
+        let checked = WriteValidator::initialize_read_only(snapshot)
+            .check_lines(lp, Time::from_timestamp_nanos(0), Precision::Nanosecond)
+            .unwrap();
+        assert_eq!(checked.line_count, 1);
+        assert_eq!(checked.errors.len(), 4);
+
+        assert_eq!(catalog.sequence_number(), sequence);
+    }
+
+    #[test]
+    fn empty_row_policy() {
+        let catalog = test_catalog();
+        let db_schema = catalog.db_or_create("test").unwrap();
+        // the parser requires a field on every line, so remove them as a transform would:
+        let empty_line = || {
+            let mut line = influxdb_line_protocol::parse_lines("cpu val=1 1")
+                .next()
+                .unwrap()
+                .unwrap();
+            line.field_set.clear();
+            line
+        };
+        let validate = |policy: EmptyRowPolicy, batch_state: &mut BatchState| {
+            let config = ValidatorConfig {
+                empty_row_policy: policy,
+                ..Default::default()
+            };
+            let mut schema = Cow::Borrowed(db_schema.as_ref());
+            let result = super::validate_and_qualify_line(
+                &mut schema,
+                0,
+                empty_line(),
+                0,
+                Precision::Nanosecond,
+                &config,
+                batch_state,
+            );
+            // the empty line must not have created a table:
+            assert!(schema.table_definition("cpu").is_none());
+            result.map(|validated| validated.is_some())
+        };
+
+        let mut batch_state = BatchState::default();
+        let err = validate(EmptyRowPolicy::Reject, &mut batch_state).unwrap_err();
+        assert_eq!(err.error_message, "line 1 has no tags or fields");
+        assert!(batch_state.warnings.is_empty());
+
+        assert!(!validate(EmptyRowPolicy::Skip, &mut batch_state).unwrap());
+        assert_eq!(
+            batch_state.warnings[0].warning_message,
+            "line 1 has no tags or fields, so was skipped"
+        );
+    }
+
+    #[test]
+    fn no_empty_rows_from_skipped_tags_or_dropped_lines() {
+        let catalog = test_catalog();
+        // skipping the only tag of a line leaves the field that the parser requires on every
+        // line, and a line with an incompatible field is dropped whole rather than losing the
+        // field, so neither leaves a row with only a timestamp:
+        let result = validate_lp(
+            &catalog,
+            |validator| {
+                validator
+                    .with_injected_tags(vec![("node".into(), "".into())])
+                    .with_skip_empty_tags()
+                    .with_incompatible_line_mode(IncompatibleLineMode::DropIncompatible)
+            },
+            "cpu usage=0.5 1\n\
+            cpu usage=\"high\" 2",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .into_inner();
+        assert!(result.errors.is_empty());
+        assert_eq!(result.dropped_incompatible_count, 1);
+        assert_eq!(result.lines.len(), 1);
+        assert_eq!(result.lines[0].row.fields.len(), 2);
+    }
+
+    #[cfg(feature = "metrics-text")]
+    #[test]
+    fn to_metrics_text() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5,idle=0.2 1\n\
+            cpu,host=b usage=\"high\" 2\n\
+            not line protocol\n\
+            cpu,host=c usage=0.9",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        let text = result.to_metrics_text();
+        for expected in [
+            "# TYPE influxdb3_write_validated_lines gauge\n",
+            "influxdb3_write_validated_lines 2\n",
+            "influxdb3_write_validated_bytes 51\n",
+            "influxdb3_write_validated_fields 3\n",
+            "influxdb3_write_validated_index_columns 2\n",
+            "influxdb3_write_validated_errors{kind=\"parse\"} 1\n",
+            "influxdb3_write_validated_errors{kind=\"validation\"} 1\n",
+            "influxdb3_write_validated_warnings 0\n",
+            "influxdb3_write_validated_defaulted_timestamps 1\n",
+            "influxdb3_write_validated_schema_changes 1\n",
+        ] {
+            assert!(text.contains(expected), "missing {expected:?} in:\n{text}");
+        }
+    }
+
+    #[test]
+    fn max_timestamp_span() {
+        let catalog = test_catalog();
+        let lp = "cpu usage=0.5 1000\n\
+            cpu usage=0.6 1500\n\
+            cpu usage=0.7 2500\n\
+            cpu usage=0.8 500\n\
+            cpu usage=0.9 3000";
+        let validate = |policy: TimestampSpanPolicy| {
+            validate_lp(
+                &catalog,
+                |validator| validator.with_max_timestamp_span(Duration::from_secs(1), policy),
+                lp,
+                Precision::Millisecond,
+            )
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m())
+        };
+
+        let result = validate(TimestampSpanPolicy::Warn);
+        assert_eq!(result.line_count, 5);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].line_number, 3);
+        assert_eq!(
+            result.warnings[0].warning_message,
+            "timestamp 2500000000 on line 3 makes the write span 1.5s, which exceeds the \
+            maximum span of 1s"
+        );
+
+        let result = validate(TimestampSpanPolicy::Reject);
+        assert_eq!(result.line_count, 3);
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| e.line_number)
+                .collect::<Vec<_>>(),
+            [3, 5]
+        );
+    }
+
+    #[test]
+    fn tag_cardinality_budgets() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| {
+                validator.with_tag_cardinality_budgets(HashMap::from([(Arc::from("user_id"), 2)]))
+            },
+            "logins,user_id=a,region=us count=1i 1\n\
+            logins,user_id=b,region=eu count=1i 2\n\
+            logins,user_id=a,region=ap count=1i 3\n\
+            logins,user_id=c,region=us count=1i 4\n\
+            signups,user_id=c count=1i 5",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 4);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].line_number, 4);
+        assert_eq!(
+            result.errors[0].error_message,
+            "tag 'user_id' on line 4 exceeds its budget of 2 distinct values in a single write"
+        );
+    }
+
+    #[test]
+    fn convert_lines_to_chunk_batches() {
+        let catalog = test_catalog();
+        let minute_ns = 60_000_000_000;
+        let lp = format!(
+            "cpu,host=a usage=0.5 {t1}\n\
+            mem,host=a free=1i 1\n\
+            cpu,host=b usage=0.7 2\n\
+            cpu,host=c usage=0.9 {t2}",
+            t1 = 6 * minute_ns,
+            t2 = 7 * minute_ns,
+        );
+        let result = validate_lp(&catalog, |validator| validator, &lp, Precision::Nanosecond)
+            .unwrap()
+            .convert_lines_to_chunk_batches(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 4);
+
+        let db_schema = catalog.db_schema("test").unwrap();
+        let cpu_id = db_schema.table_name_to_id("cpu").unwrap();
+        let mem_id = db_schema.table_name_to_id("mem").unwrap();
+        let chunks = result
+            .valid_data
+            .iter()
+            .map(|(chunk_time, batch)| {
+                let mut tables = batch
+                    .table_chunks
+                    .iter()
+                    .map(|(table_id, chunks)| {
+                        assert_eq!(
+                            chunks.chunk_time_to_chunk.keys().collect::<Vec<_>>(),
+                            [chunk_time]
+                        );
+                        (*table_id, chunks.row_count())
+                    })
+                    .collect::<Vec<_>>();
+                tables.sort();
+                (*chunk_time, tables)
+            })
+            .collect::<Vec<_>>();
+        let mut first_chunk = vec![(cpu_id, 1), (mem_id, 1)];
+        first_chunk.sort();
+        assert_eq!(
+            chunks,
+            [(0, first_chunk), (5 * minute_ns, vec![(cpu_id, 2)])]
+        );
+    }
+
+    #[test]
+    fn verify_column_ids() {
+        let lp = "cpu,host=a usage=0.5 1";
+        let validate = |catalog: &Arc<Catalog>| {
+            validate_lp(
+                catalog,
+                |validator| validator.with_column_id_verification(),
+                lp,
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .into_inner()
+        };
+        let catalog = test_catalog();
+        let lines = validate(&catalog).lines;
+        super::verify_column_ids(&catalog.db_schema("test").unwrap(), &lines).unwrap();
+
+        // a schema that has the same table and columns, but created independently, so with
+        // different ids, is like a stale schema that the rows have diverged from:
+        let other_catalog = test_catalog();
+        validate(&other_catalog);
+        let err = super::verify_column_ids(&other_catalog.db_schema("test").unwrap(), &lines)
+            .unwrap_err();
+        assert!(matches!(err, Error::ColumnNotInCatalog { .. }), "{err}");
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        let lp = "# exported by some tool\n\
+            \n\
+            cpu,host=a usage=0.5 1\n\
+            \x20\x20# an indented comment\n\
+            cpu,host=b usage=\"high\" 2\n\
+            \t\n\
+            cpu,host=c usage=0.7 3\r\n\
+            \n\
+            not line protocol\n\
+            log,host=a msg=\"multi\nline\" 4\n\
+            cpu,host=d usage=\"high\" 5\n";
+        let result = validate_lp(&catalog, |validator| validator, lp, Precision::Nanosecond)
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 3);
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| (e.line_number, e.original_line.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (5, "cpu,host=b usage=\"high\" 2"),
+                (9, "not line protocol"),
+                (12, "cpu,host=d usage=\"high\" 5"),
+            ]
+        );
+
+        let checked = WriteValidator::initialize(namespace, Arc::clone(&catalog), 0)
+            .unwrap()
+            .check_lines(lp, Time::from_timestamp_nanos(0), Precision::Nanosecond)
+            .unwrap();
+        assert_eq!(checked.line_count, 3);
+        assert_eq!(
+            checked
+                .errors
+                .iter()
+                .map(|e| e.line_number)
+                .collect::<Vec<_>>(),
+            [5, 9, 12]
+        );
+    }
+
+    #[test]
+    fn drop_incompatible_lines() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5 1",
+            Precision::Nanosecond,
+        )
+        .unwrap();
+        let sequence = catalog.sequence_number();
+        let lp = "cpu,host=b usage=0.7 2\n\
+            cpu,host=b usage=\"high\" 3\n\
+            cpu,host=b,region=us usage=0.7 4\n\
+            cpu,host=b usage=0.7,idle=0.2 5\n\
+            mem,host=a free=1i 6\n\
+            cpu,host=c usage=0.9 7";
+
+        let result = validate_lp(
+            &catalog,
+            |validator| {
+                validator.with_incompatible_line_mode(IncompatibleLineMode::DropIncompatible)
+            },
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 2);
+        assert_eq!(result.dropped_incompatible_count, 4);
+        assert!(result.errors.is_empty());
+        assert!(result.catalog_updates.is_none());
+        assert_eq!(catalog.sequence_number(), sequence);
+
+        let checked = WriteValidator::initialize(namespace, Arc::clone(&catalog), 0)
+            .unwrap()
+            .with_incompatible_line_mode(IncompatibleLineMode::DropIncompatible)
+            .check_lines(lp, Time::from_timestamp_nanos(0), Precision::Nanosecond)
+            .unwrap();
+        assert_eq!(checked.line_count, 2);
+        assert_eq!(checked.dropped_incompatible_count, 4);
+        assert!(checked.errors.is_empty());
+    }
+
+    #[test]
+    fn time_source() {
+        struct ScriptedTime(std::cell::Cell<i64>);
+
+        impl super::TimeSource for ScriptedTime {
+            fn now_ns(&self) -> i64 {
+                let now = self.0.get();
+                self.0.set(now + 1_000);
+                now
+            }
+        }
+
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        let lp = "cpu,host=a usage=0.5\ncpu,host=b usage=0.7";
+
+        // the source is read once per write, so every defaulted timestamp is the same:
+        let clock = ScriptedTime(std::cell::Cell::new(42));
+        let mut validator =
+            WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog), 0).unwrap();
+        for expected in [42, 1_042] {
+            let result = validator
+                .parse_lines_and_update_schema_reusing(lp, false, &clock, Precision::Nanosecond)
+                .unwrap()
+                .into_inner();
+            assert_eq!(
+                result.lines.iter().map(|l| l.row.time).collect::<Vec<_>>(),
+                [expected, expected]
+            );
+        }
+
+        // a time provider can be used as the source:
+        let provider: Arc<dyn iox_time::TimeProvider> =
+            Arc::new(iox_time::MockProvider::new(Time::from_timestamp_nanos(7)));
+        let result = WriteValidator::initialize(namespace, Arc::clone(&catalog), 0)
+            .unwrap()
+            .parse_lines_and_update_schema(lp, false, provider, Precision::Nanosecond)
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            result.lines.iter().map(|l| l.row.time).collect::<Vec<_>>(),
+            [7, 7]
+        );
+    }
+
+    #[test]
+    fn validated_schema() {
+        let catalog = test_catalog();
+        let validate = |lp: &str| {
+            validate_lp(&catalog, |validator| validator, lp, Precision::Nanosecond).unwrap()
+        };
+        validate("cpu,host=a usage=0.5 1");
+
+        // a write that changes the schema exposes the schema with its changes:
+        let lines_parsed = validate("cpu,host=a,region=us usage=0.5 2\nmem,host=a free=1i 3");
+        let schema = Arc::clone(lines_parsed.into_inner().validated_schema());
+        let cpu = schema.table_definition("cpu").unwrap();
+        assert!(cpu.column_name_to_id("region").is_some());
+        assert!(schema.table_definition("mem").is_some());
+        assert_eq!(*schema, *catalog.db_schema("test").unwrap());
+
+        // a write that does not change the schema exposes the schema it was validated against:
+        let validated =
+            validate("cpu,host=b usage=0.7 4").convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(*validated.validated_schema, *schema);
+    }
+
+    #[test]
+    fn min_timestamp() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        let lp = "cpu,host=a usage=0.5 -1\n\
+            cpu,host=b usage=0.6 0\n\
+            cpu,host=c usage=0.7 1\n\
+            cpu,host=d usage=0.8";
+        let validate = |ingest_time_ns: i64| {
+            WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog), 0)
+                .unwrap()
+                .with_min_timestamp(0)
+                .parse_lines_and_update_schema(lp, true, ingest_time_ns, Precision::Nanosecond)
+                .unwrap()
+                .into_inner()
+        };
+
+        let result = validate(10);
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| (e.line_number, e.error_message.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    1,
+                    "timestamp -1 on line 1 is not after the minimum timestamp 0"
+                ),
+                (
+                    2,
+                    "timestamp 0 on line 2 is not after the minimum timestamp 0"
+                ),
+            ]
+        );
+        assert_eq!(
+            result.lines.iter().map(|l| l.row.time).collect::<Vec<_>>(),
+            [1, 10]
+        );
+
+        // the check applies to the ingest time given to lines without a timestamp:
+        let result = validate(0);
+        assert_eq!(result.errors.len(), 3);
+        assert_eq!(result.errors[2].line_number, 4);
+    }
+
+    #[test]
+    fn validated_lines_bytes_round_trip() {
+        let catalog = test_catalog();
+        let validated = validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5,count=1i,ok=true,name=\"a\" 1\n\
+            cpu,host=b usage=\"bad\" 2\n\
+            mem,host=a free=1i 3",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert!(validated.catalog_updates.is_some());
+        assert_eq!(validated.errors.len(), 1);
+
+        let bytes = validated.to_bytes().unwrap();
+        let schema = catalog.db_schema("test").unwrap();
+        let restored = super::ValidatedLines::<influxdb3_wal::WriteBatch>::from_bytes(
+            &bytes,
+            Arc::clone(&schema),
+        )
+        .unwrap();
+        assert_eq!(restored.line_count, validated.line_count);
+        assert_eq!(restored.valid_bytes_count, validated.valid_bytes_count);
+        assert_eq!(restored.errors.len(), 1);
+        assert_eq!(
+            restored.errors[0].line_number,
+            validated.errors[0].line_number
+        );
+        assert_eq!(
+            restored.errors[0].error_message,
+            validated.errors[0].error_message
+        );
+        assert_eq!(restored.catalog_updates, validated.catalog_updates);
+        // the schema is the one given when deserializing:
+        assert!(Arc::ptr_eq(&restored.validated_schema, &schema));
+        // the rows keep their ids and the order of their fields:
+        assert_eq!(restored.valid_data, validated.valid_data);
+
+        assert!(matches!(
+            super::ValidatedLines::<influxdb3_wal::WriteBatch>::from_bytes(
+                &bytes[..bytes.len() / 2],
+                schema
+            ),
+            Err(Error::ValidatedLinesSerialization(_))
+        ));
+    }
+
+    #[test]
+    fn frozen_schema() {
+        let catalog = test_catalog();
+        let write = |lp: &str| {
+            validate_lp(&catalog, |validator| validator, lp, Precision::Nanosecond).unwrap();
+        };
+        write("cpu,host=a usage=0.5 1");
+        let frozen = catalog.db_schema("test").unwrap();
+        let mut validator = WriteValidator::initialize_frozen(frozen, Arc::clone(&catalog), 0);
+
+        // another writer changes the schema after it was frozen:
+        write("cpu,host=a,region=us usage=0.5 2\nmem,host=a free=1i 3");
+
+        // columns can be created against the frozen schema, and are added to the catalog:
+        let lines_parsed = validator
+            .parse_lines_and_update_schema_reusing(
+                "cpu,host=a usage=0.5,idle=0.1 4",
+                false,
+                Time::from_timestamp_nanos(0),
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .into_inner();
+        let schema = lines_parsed.validated_schema();
+        assert!(schema.table_definition("mem").is_none());
+        let cpu = schema.table_definition("cpu").unwrap();
+        assert!(cpu.column_name_to_id("region").is_none());
+        let idle_id = cpu.column_name_to_id("idle").unwrap();
+        let catalog_cpu = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        assert_eq!(catalog_cpu.column_name_to_id("idle"), Some(idle_id));
+        assert!(catalog_cpu.column_name_to_id("region").is_some());
+
+        // the next write sees the changes made by the validator, but not by others:
+        let lines_parsed = validator
+            .parse_lines_and_update_schema_reusing(
+                "cpu,host=b usage=0.7,idle=0.2 5",
+                false,
+                Time::from_timestamp_nanos(0),
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .into_inner();
+        assert!(lines_parsed.catalog_batch.is_none());
+
+        // creating a column or table that another writer created since is a conflict:
+        for lp in ["cpu,host=a,region=eu usage=0.5 6", "mem,host=b free=2i 7"] {
+            let sequence = catalog.sequence_number();
+            let err = validator
+                .parse_lines_and_update_schema_reusing(
+                    lp,
+                    false,
+                    Time::from_timestamp_nanos(0),
+                    Precision::Nanosecond,
+                )
+                .unwrap_err();
+            assert!(
+                matches!(err, Error::FrozenSchemaConflict { .. }),
+                "unexpected error: {err}"
+            );
+            assert_eq!(catalog.sequence_number(), sequence);
+        }
+    }
+
+    #[test]
+    fn skip_empty_tags() {
+        let catalog = test_catalog();
+        let validate = |node: &str, lp: &str| {
+            validate_lp(
+                &catalog,
+                |validator| {
+                    validator
+                        .with_injected_tags(vec![("node".into(), node.into())])
+                        .with_skip_empty_tags()
+                },
+                lp,
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .into_inner()
+        };
+
+        // the tag is empty in every line, so no column is created for it in a new table, or an
+        // existing one:
+        let result = validate("", "cpu,host=a usage=0.5 1\ncpu,host=b usage=0.7 2");
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(
+            result.warnings[0].warning_message,
+            "tag 'node' on line 1 is empty in every line of the write, so no column was created \
+            for it"
+        );
+        let result = validate("", "cpu,host=a usage=0.5,idle=0.1 3");
+        assert_eq!(result.warnings.len(), 1);
+        let cpu = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        assert!(cpu.column_name_to_id("idle").is_some());
+        assert!(cpu.column_name_to_id("node").is_none());
+        assert!(result.lines.iter().all(|l| l.row.fields.len() == 4));
+
+        // once the tag has a column, empty values are written to it:
+        validate("n1", "cpu,host=a usage=0.5 4");
+        let result = validate("", "cpu,host=a usage=0.5 5");
+        assert!(result.warnings.is_empty());
+        let node_id = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap()
+            .column_name_to_id("node")
+            .unwrap();
+        assert!(matches!(
+            &result.lines[0].row.fields.iter().find(|f| f.id == node_id).unwrap().value,
+            FieldData::Tag(v) if v.is_empty()
+        ));
+
+        // the warning is for the first line that skips the tag and is accepted:
+        validate("", "disk,host=a used=1i 6");
+        let result = validate(
+            "",
+            "disk,host=a used=\"full\" 7\n\
+            disk,host=b used=2i 8",
+        );
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(
+            result
+                .warnings
+                .iter()
+                .map(|w| w.line_number)
+                .collect::<Vec<_>>(),
+            [2]
+        );
+    }
+
+    #[test]
+    fn content_checksum() {
+        let catalog = test_catalog();
+        let validate = |lp: &str| {
+            validate_lp(&catalog, |validator| validator, lp, Precision::Nanosecond)
+                .unwrap()
+                .convert_lines_to_buffer(Gen1Duration::new_5m())
+        };
+        let checksum = validate(
+            "cpu,host=a usage=0.5,count=1i 1\n\
+            mem,host=a free=2i 2\n\
+            cpu,host=b usage=0.7,count=3i 3",
+        )
+        .content_checksum();
+
+        // the checksum does not depend on the order of rows or fields:
+        let reordered = validate(
+            "cpu,host=b count=3i,usage=0.7 3\n\
+            cpu,host=a usage=0.5,count=1i 1\n\
+            mem,host=a free=2i 2",
+        );
+        assert_eq!(reordered.content_checksum(), checksum);
+        assert_eq!(super::write_batch_checksum(&reordered.valid_data), checksum);
+
+        let changed = validate(
+            "cpu,host=a usage=0.5,count=1i 1\n\
+            mem,host=a free=2i 2\n\
+            cpu,host=b usage=0.7,count=4i 3",
+        );
+        assert_ne!(changed.content_checksum(), checksum);
+    }
+
+    #[test]
+    fn new_table_line_count() {
+        let catalog = test_catalog();
+        let validate = |lp: &str| {
+            validate_lp(&catalog, |validator| validator, lp, Precision::Nanosecond)
+                .unwrap()
+                .into_inner()
+        };
+        validate("cpu,host=a usage=0.5 1");
+
+        let result = validate(
+            "cpu,host=a usage=0.5 2\n\
+            mem,host=a free=1i 3\n\
+            mem,host=b free=2i 4\n\
+            disk,host=a used=3i 5",
+        );
+        assert_eq!(
+            result
+                .lines
+                .iter()
+                .map(|l| l.table_created)
+                .collect::<Vec<_>>(),
+            [false, true, false, true]
+        );
+        let result =
+            WriteValidator { state: result }.convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.new_table_line_count, 2);
+    }
+
+    #[test]
+    fn max_string_field_bytes() {
+        let catalog = test_catalog();
+        let validate = |policy: StringLengthPolicy, lp: &str| {
+            validate_lp(
+                &catalog,
+                |validator| validator.with_max_string_field_bytes(5, policy),
+                lp,
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .into_inner()
+        };
+        let lp = "cpu,host=a name=\"short\" 1\n\
+            cpu,host=b name=\"too long\" 2\n\
+            mem,host=a name=\"abcd\u{e9}f\" 3";
+
+        let result = validate(StringLengthPolicy::Reject, lp);
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| e.error_message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "string field 'name' on line 2 is 8 bytes, which exceeds the maximum of 5 bytes",
+                "string field 'name' on line 3 is 7 bytes, which exceeds the maximum of 5 bytes",
+            ]
+        );
+        assert_eq!(result.lines.len(), 1);
+
+        // values are truncated at a character boundary:
+        let result = validate(StringLengthPolicy::Truncate, lp);
+        assert!(result.errors.is_empty());
+        assert_eq!(
+            result
+                .warnings
+                .iter()
+                .map(|w| w.warning_message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "string field 'name' on line 2 was truncated from 8 to 5 bytes",
+                "string field 'name' on line 3 was truncated from 7 to 4 bytes",
+            ]
+        );
+        let names = result
+            .lines
+            .iter()
+            .map(|l| {
+                l.row
+                    .fields
+                    .iter()
+                    .find_map(|f| match &f.value {
+                        FieldData::String(v) => Some(v.as_str()),
+                        _ => None,
+                    })
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["short", "too l", "abcd"]);
+
+        // a line that is rejected after its value is truncated has no warning:
+        let result = validate(
+            StringLengthPolicy::Truncate,
+            "cpu,host=c name=\"too long\",count=1i 4\n\
+            cpu,host=d name=\"too long\",count=\"many\" 5",
+        );
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].line_number, 2);
+        assert_eq!(
+            result
+                .warnings
+                .iter()
+                .map(|w| w.line_number)
+                .collect::<Vec<_>>(),
+            [1]
+        );
+    }
+
+    #[test]
+    fn expected_column_ids() {
+        let catalog = test_catalog();
+        let validate = |expected: HashMap<Arc<str>, HashMap<Arc<str>, _>>, lp: &str| {
+            validate_lp(
+                &catalog,
+                |validator| validator.with_expected_column_ids(expected),
+                lp,
+                Precision::Nanosecond,
+            )
+        };
+        validate(HashMap::new(), "cpu,host=a usage=0.5 1").unwrap();
+        let cpu = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        let host_id = cpu.column_name_to_id("host").unwrap();
+        let usage_id = cpu.column_name_to_id("usage").unwrap();
+
+        validate(
+            HashMap::from([(
+                "cpu".into(),
+                HashMap::from([("host".into(), host_id), ("usage".into(), usage_id)]),
+            )]),
+            "cpu,host=b usage=0.7 2",
+        )
+        .unwrap();
+
+        let sequence = catalog.sequence_number();
+        let err = validate(
+            HashMap::from([("cpu".into(), HashMap::from([("usage".into(), host_id)]))]),
+            "cpu,host=b usage=0.7,idle=0.1 3",
+        )
+        .unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                Error::ColumnIdMismatch { table_name, column_name, expected, actual }
+                    if table_name == "cpu"
+                        && column_name == "usage"
+                        && *expected == host_id
+                        && *actual == usage_id
+            ),
+            "unexpected error: {err}"
+        );
+        // the catalog is not updated when the ids do not match:
+        assert_eq!(catalog.sequence_number(), sequence);
+    }
+
+    #[test]
+    fn split_lp() {
+        let lp = "cpu,host=a usage=0.5 1\n\
+            # a comment\n\
+            cpu,host=b name=\"two\nlines\" 2\n\
+            \n\
+            cpu,host=c usage=0.7 3\r\n\
+            cpu,host=d usage=0.9 4\n\
+            cpu,host=e usage=1.1 5";
+        let batches = super::split_lp(lp, 2).collect::<Vec<_>>();
+        assert_eq!(
+            batches,
+            [
+                "cpu,host=a usage=0.5 1\n# a comment\ncpu,host=b name=\"two\nlines\" 2\n\n",
+                "cpu,host=c usage=0.7 3\r\ncpu,host=d usage=0.9 4\n",
+                "cpu,host=e usage=1.1 5",
+            ]
+        );
+        assert_eq!(batches.concat(), lp);
+
+        // each sub-batch can be validated on its own:
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        let mut validator = WriteValidator::initialize(namespace, Arc::clone(&catalog), 0).unwrap();
+        let line_counts = batches
+            .iter()
+            .map(|batch| {
+                validator
+                    .parse_lines_and_update_schema_reusing(
+                        batch,
+                        false,
+                        Time::from_timestamp_nanos(0),
+                        Precision::Nanosecond,
+                    )
+                    .unwrap()
+                    .into_inner()
+                    .lines
+                    .len()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(line_counts, [2, 2, 1]);
+
+        // a line is never split, and a batch of one line is yielded whole:
+        let long_line = format!("cpu,host=a name=\"{}\" 1", "x\n".repeat(1_000));
+        assert_eq!(
+            super::split_lp(&long_line, 1).collect::<Vec<_>>(),
+            [long_line.as_str()]
+        );
+        assert_eq!(super::split_lp("", 1).count(), 0);
+    }
+
+    #[test]
+    fn measurement_case_policy() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_measurement_case_policy(MeasurementCasePolicy::Lowercase),
+            "CPU,host=a usage=0.5 1\n\
+            cpu,host=b usage=0.7 2\n\
+            Cpu,host=c usage=0.9 3",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .into_inner();
+
+        let db_schema = catalog.db_schema("test").unwrap();
+        assert_eq!(db_schema.table_names(), [Arc::<str>::from("cpu")]);
+        let cpu_id = db_schema.table_name_to_id("cpu").unwrap();
+        assert!(result.lines.iter().all(|l| l.table_id == cpu_id));
+        assert_eq!(result.lines.len(), 3);
+    }
+
+    #[test]
+    fn row_validator() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| {
+                validator.with_row_validator(|row, table_def| {
+                    let Some(col_id) = table_def.column_name_to_id("temperature") else {
+                        return Ok(());
+                    };
+                    match row.fields.iter().find(|f| f.id == col_id).map(|f| &f.value) {
+                        Some(FieldData::Float(v)) if !(-50.0..150.0).contains(v) => {
+                            Err(format!("temperature {v} is out of range"))
+                        }
+                        _ => Ok(()),
+                    }
+                })
+            },
+            "weather,city=a temperature=200 1\n\
+            weather,city=a humidity=0.5 2\n\
+            weather,city=b humidity=0.6,temperature=-80 3\n\
+            weather,city=c humidity=0.7,temperature=20 4",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .into_inner();
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| (e.line_number, e.error_message.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    1,
+                    "row on line 1 was rejected: temperature 200 is out of range"
+                ),
+                (
+                    3,
+                    "row on line 3 was rejected: temperature -80 is out of range"
+                ),
+            ]
+        );
+        assert_eq!(result.lines.len(), 2);
+        // a rejected row does not change the schema, so the table was created by line 2:
+        assert!(result.lines[0].table_created);
+        let weather = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("weather")
+            .unwrap();
+        assert!(weather.column_name_to_id("temperature").is_some());
+    }
+
+    #[test]
+    fn tables_touched() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| validator,
+            "mem,host=a free=1i 1\n\
+            cpu,host=a usage=0.5 2\n\
+            mem,host=b free=2i 3\n\
+            disk,host=a used=3i 4\n\
+            cpu,host=b usage=0.7 5",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+
+        let db_schema = catalog.db_schema("test").unwrap();
+        let expected = ["mem", "cpu", "disk"].map(|name| db_schema.table_name_to_id(name).unwrap());
+        assert_eq!(result.tables_touched, expected);
+    }
+
+    #[test]
+    fn out_of_order_row_count() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5 10\n\
+            mem,host=a free=1i 5\n\
+            cpu,host=a usage=0.6 20\n\
+            cpu,host=b usage=0.7 15\n\
+            mem,host=b free=2i 6\n\
+            cpu,host=c usage=0.8 20\n\
+            cpu,host=d usage=0.9 1",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+
+        // rows are not rejected for being out of order:
+        assert_eq!(result.line_count, 7);
+        let cpu_id = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_name_to_id("cpu")
+            .unwrap();
+        assert_eq!(result.out_of_order_row_count, HashMap::from([(cpu_id, 2)]));
+    }
+
+    #[test]
+    fn catalog_apply_failure_rolls_back() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        let write = {
+            let catalog = Arc::clone(&catalog);
+            let namespace = namespace.clone();
+            move |lp: &str| {
+                validate_lp(&catalog, |validator| validator, lp, Precision::Nanosecond).unwrap();
+            }
+        };
+        write("mem,host=a free=1i 1");
+
+        // inject a failure when applying the catalog batch, by having another writer create
+        // the same table while the lines are being validated:
+        let mut validator = WriteValidator::initialize(namespace, Arc::clone(&catalog), 0)
+            .unwrap()
+            .with_on_table_created(move |_, table_name| {
+                if table_name == "cpu" {
+                    write("cpu,host=z usage=0.1 1");
+                }
+            });
+        let schema_before = Arc::clone(&validator.state.db_schema);
+        let err = validator
+            .parse_lines_and_update_schema_reusing(
+                "mem,host=a free=2i,used=3i 2\n\
+                cpu,host=a usage=0.5 3\n\
+                disk,host=a used=1i 4",
+                false,
+                Time::from_timestamp_nanos(0),
+                Precision::Nanosecond,
+            )
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::CatalogUpdateError(_)),
+            "unexpected error: {err}"
+        );
+
+        // only the injected change was made to the catalog:
+        let db_schema = catalog.db_schema("test").unwrap();
+        assert!(db_schema.table_definition("cpu").is_some());
+        assert!(db_schema.table_definition("disk").is_none());
+        let mem = db_schema.table_definition("mem").unwrap();
+        assert!(mem.column_name_to_id("used").is_none());
+        // and the validator has none of the changes made by the failed write:
+        assert!(Arc::ptr_eq(&validator.state.db_schema, &schema_before));
+
+        // so it can be reused, once it sees the table created by the other writer:
+        let lines_parsed = validator
+            .parse_lines_and_update_schema_reusing(
+                "cpu,host=a usage=0.5 3",
+                false,
+                Time::from_timestamp_nanos(0),
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            lines_parsed.lines[0].table_id,
+            db_schema.table_name_to_id("cpu").unwrap()
+        );
+    }
+
+    #[test]
+    fn series_key_missing_policy() {
+        let catalog = test_catalog();
+        let validate = |policy: SeriesKeyMissingPolicy, lp: &str| {
+            validate_lp(
+                &catalog,
+                |validator| validator.with_series_key_missing_policy(policy),
+                lp,
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .into_inner()
+        };
+        // the table has a series key of two tags:
+        validate(
+            SeriesKeyMissingPolicy::Allow,
+            "cpu,host=a,region=us usage=0.5 1",
+        );
+        let cpu = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        assert_eq!(cpu.series_key_ids().len(), 2);
+        let lp = "cpu usage=0.7 2\ncpu,host=b usage=0.9 3";
+
+        let result = validate(SeriesKeyMissingPolicy::Reject, lp);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(
+            result.errors[0].error_message,
+            "line 1 has none of the series key tags of table 'cpu': host, region"
+        );
+        assert_eq!(result.lines.len(), 1);
+
+        let result = validate(SeriesKeyMissingPolicy::EmptyDefaults, lp);
+        assert!(result.errors.is_empty());
+        let tag_values = |fields: &[influxdb3_wal::Field]| {
+            cpu.series_key_ids()
+                .iter()
+                .map(|col_id| {
+                    fields
+                        .iter()
+                        .find(|f| f.id == *col_id)
+                        .map(|f| match &f.value {
+                            FieldData::Tag(v) => v.clone(),
+                            _ => panic!("series key column is not a tag"),
+                        })
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            tag_values(&result.lines[0].row.fields),
+            [Some(String::new()), Some(String::new())]
+        );
+        // a line with some of the series key tags is written as is:
+        assert_eq!(
+            tag_values(&result.lines[1].row.fields),
+            [Some("b".to_string()), None]
+        );
+    }
 
-    use super::WriteValidator;
-    use crate::{Precision, write_buffer::Error};
+    #[test]
+    fn error_sample() {
+        let catalog = test_catalog();
+        let lp = "cpu,host=a usage=0.5 1\n\
+            cpu,host=b usage=\"bad\" 2\n\
+            # a comment\n\
+            cpu,host=c usage=0.7 3\n\
+            not line protocol\n\
+            cpu,host=d usage=0.9 4\n\
+            cpu,host=e usage=true 5";
+        let result = validate_lp(&catalog, |validator| validator, lp, Precision::Nanosecond)
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m());
 
-    use data_types::NamespaceName;
-    use influxdb3_catalog::catalog::Catalog;
-    use influxdb3_id::TableId;
-    use influxdb3_wal::Gen1Duration;
-    use iox_time::Time;
+        let sample = result.error_sample(2, Some(lp));
+        assert_eq!(sample.total_count, 3);
+        assert_eq!(
+            sample
+                .errors
+                .iter()
+                .map(|e| (e.error.line_number, e.line_before, e.line_after))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    2,
+                    Some("cpu,host=a usage=0.5 1"),
+                    Some("cpu,host=c usage=0.7 3")
+                ),
+                (
+                    5,
+                    Some("cpu,host=c usage=0.7 3"),
+                    Some("cpu,host=d usage=0.9 4")
+                ),
+            ]
+        );
+
+        // the last line has no line after it, and context is only given if requested:
+        let sample = result.error_sample(10, Some(lp));
+        assert_eq!(sample.errors.len(), 3);
+        assert_eq!(sample.errors[2].line_after, None);
+        let sample = result.error_sample(10, None);
+        assert!(
+            sample
+                .errors
+                .iter()
+                .all(|e| e.line_before.is_none() && e.line_after.is_none())
+        );
+    }
 
     #[test]
-    fn write_validator() -> Result<(), Error> {
-        let node_id = Arc::from("sample-host-id");
-        let instance_id = Arc::from("sample-instance-id");
+    fn adversarial_input_does_not_panic() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        let validate_with_precision = |lp: &str, precision: Precision| {
+            validate_lp(&catalog, |validator| validator, lp, precision)
+                .map(|validated| validated.convert_lines_to_buffer(Gen1Duration::new_5m()))
+        };
+        let validate = |lp: &str| validate_with_precision(lp, Precision::Nanosecond);
+        let precisions = [
+            Precision::Auto,
+            Precision::AutoBatch,
+            Precision::Second,
+            Precision::Millisecond,
+            Precision::Microsecond,
+            Precision::Nanosecond,
+        ];
+
+        // lines that reuse a column name, or have more columns than a table can hold, are
+        // rejected rather than reaching the catalog:
+        let too_many_columns = format!(
+            "wide {}",
+            (0..600)
+                .map(|i| format!("f{i}=1"))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        let lp = format!(
+            "dup,a=1 a=2\n\
+            dup,b=1,b=2 f=1\n\
+            dup f=1,f=2\n\
+            dup,c=1 f=1\n\
+            {too_many_columns}"
+        );
+        let result = validate(&lp).unwrap();
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| e.line_number)
+                .collect::<Vec<_>>(),
+            [1, 2, 3, 5]
+        );
+        assert_eq!(result.line_count, 1);
+
+        // timestamps that overflow once converted to nanoseconds are rejected:
+        let lp = "cpu f=1 9223372036854775807\n\
+            cpu f=1 -9223372036854775808\n\
+            cpu f=1 9223372036855";
+        for precision in precisions {
+            let result = validate_with_precision(lp, precision).unwrap();
+            let expected_errors = match precision {
+                Precision::Second | Precision::Millisecond => vec![1, 2, 3],
+                Precision::Microsecond => vec![1, 2],
+                Precision::Auto | Precision::AutoBatch | Precision::Nanosecond => vec![],
+            };
+            assert_eq!(
+                result
+                    .errors
+                    .iter()
+                    .map(|e| e.line_number)
+                    .collect::<Vec<_>>(),
+                expected_errors,
+                "{precision:?}"
+            );
+        }
+
+        // random input, biased towards the characters that are significant to line protocol:
+        let alphabet = b"cpu,=\" \\\n\r#tf0123456789.-iu";
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..500 {
+            let len = (next() % 64) as usize;
+            let bytes = (0..len)
+                .map(|_| match next() % 8 {
+                    0 => next() as u8,
+                    _ => alphabet[(next() % alphabet.len() as u64) as usize],
+                })
+                .collect::<Vec<_>>();
+            let lp = String::from_utf8_lossy(&bytes);
+            for precision in precisions {
+                let _ = validate_with_precision(&lp, precision);
+                let _ = WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog), 0)
+                    .unwrap()
+                    .check_lines(&lp, Time::from_timestamp_nanos(0), precision);
+            }
+        }
+    }
+
+    #[test]
+    fn convert_lines_to_series_groups() {
+        let catalog = test_catalog();
+        let minute_ns = 60_000_000_000;
+        let lp = format!(
+            "cpu,host=b usage=0.1 1\n\
+            cpu,host=a usage=0.2 2\n\
+            cpu,host=b usage=0.3 3\n\
+            cpu,host=a usage=0.4 {t}\n\
+            cpu usage=0.5 4\n\
+            cpu,host=a usage=0.6 5",
+            t = 6 * minute_ns,
+        );
+        let result = validate_lp(&catalog, |validator| validator, &lp, Precision::Nanosecond)
+            .unwrap()
+            .convert_lines_to_series_groups(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 6);
+
+        let db_schema = catalog.db_schema("test").unwrap();
+        let cpu = db_schema.table_definition("cpu").unwrap();
+        let host_id = cpu.column_name_to_id("host").unwrap();
+        let series_key = |host: &str| {
+            encode_series_key(
+                &cpu,
+                &[Field::new(host_id, FieldData::Tag(host.to_string()))],
+            )
+        };
+        let groups = result
+            .valid_data
+            .iter()
+            .flat_map(|(table_id, chunks)| {
+                assert_eq!(*table_id, cpu.table_id);
+                chunks.iter().map(|(chunk_time, series)| {
+                    (
+                        *chunk_time,
+                        series
+                            .iter()
+                            .map(|(key, rows)| {
+                                (key.clone(), rows.iter().map(|r| r.time).collect::<Vec<_>>())
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        // series are ordered by their encoded key, with the rows without a host first:
+        assert_eq!(
+            groups,
+            [
+                (
+                    0,
+                    vec![
+                        (vec![], vec![4]),
+                        (series_key("a"), vec![2, 5]),
+                        (series_key("b"), vec![1, 3]),
+                    ]
+                ),
+                (5 * minute_ns, vec![(series_key("a"), vec![6 * minute_ns])]),
+            ]
+        );
+    }
+
+    #[test]
+    fn database_default_tags() {
+        let catalog = test_catalog();
+        let db_id = catalog.db_or_create("test").unwrap().id;
+        catalog
+            .apply_catalog_batch(&create::catalog_batch(
+                db_id,
+                "test",
+                0,
+                [create::set_default_tags_op(
+                    db_id,
+                    "test",
+                    [("environment".into(), "prod".into())],
+                )],
+            ))
+            .unwrap();
+
+        let result = validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5 1\n\
+            cpu,host=b,environment=staging usage=0.7 2",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 2);
+        // default tags are not written by the client, so only count towards the index count
+        // when the line sets them:
+        assert_eq!(result.index_count, 3);
+
+        let db_schema = catalog.db_schema("test").unwrap();
+        let cpu = db_schema.table_definition("cpu").unwrap();
+        let environment_id = cpu.column_name_to_id("environment").unwrap();
+        // the value set by a line takes precedence over the default:
+        let environments = result
+            .valid_data
+            .table_chunks
+            .values()
+            .flat_map(|chunks| chunks.chunk_time_to_chunk.values())
+            .flat_map(|chunk| chunk.rows.iter())
+            .map(|row| {
+                row.fields
+                    .iter()
+                    .find(|f| f.id == environment_id)
+                    .map(|f| f.value.clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            environments,
+            [
+                Some(FieldData::Tag("prod".to_string())),
+                Some(FieldData::Tag("staging".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn phase_durations() {
+        let catalog = test_catalog();
+        let lp = "cpu,host=a usage=0.5 1\nmem,host=a free=1i 2";
+        let result = validate_lp(&catalog, |validator| validator, lp, Precision::Nanosecond)
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.phase_durations, None);
+
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_phase_durations(),
+            lp,
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 2);
+        let durations = result.phase_durations.unwrap();
+        assert!(
+            durations.parse + durations.catalog_apply + durations.chunking
+                < Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn column_type_hints() {
+        let catalog = test_catalog();
+        let hints = HashMap::from([
+            (
+                Arc::<str>::from("usage"),
+                InfluxColumnType::Field(InfluxFieldType::Float),
+            ),
+            (Arc::<str>::from("region"), InfluxColumnType::Tag),
+        ]);
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_column_type_hints(hints.clone()),
+            "cpu,host=a usage=5i 1\n\
+            cpu,host=b usage=7u 2\n\
+            cpu,host=c usage=0.5 3\n\
+            cpu,host=d region=1i 4",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 3);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].line_number, 4);
+        assert_eq!(
+            result.errors[0].error_message,
+            "invalid field value in line protocol for field 'region' on line 4: expected type \
+            iox::column_type::tag, but got iox::column_type::field::integer"
+        );
+
+        // the column was created as a float, even though the first value was an integer:
+        let db_schema = catalog.db_schema("test").unwrap();
+        let cpu = db_schema.table_definition("cpu").unwrap();
+        assert_eq!(
+            cpu.field_type_by_name("usage"),
+            Some(InfluxColumnType::Field(InfluxFieldType::Float))
+        );
+        let usage_id = cpu.column_name_to_id("usage").unwrap();
+        let usages = result
+            .valid_data
+            .table_chunks
+            .values()
+            .flat_map(|chunks| chunks.chunk_time_to_chunk.values())
+            .flat_map(|chunk| chunk.rows.iter())
+            .flat_map(|row| row.fields.iter().filter(|f| f.id == usage_id))
+            .map(|f| f.value.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            usages,
+            [
+                FieldData::Float(5.0),
+                FieldData::Float(7.0),
+                FieldData::Float(0.5)
+            ]
+        );
+
+        // a hint that conflicts with an existing column is an error:
+        let hints = HashMap::from([(
+            Arc::<str>::from("usage"),
+            InfluxColumnType::Field(InfluxFieldType::Integer),
+        )]);
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_column_type_hints(hints),
+            "cpu,host=a usage=1i 5",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 0);
+        assert_eq!(
+            result.errors[0].error_message,
+            "column 'usage' on line 1 has type iox::column_type::field::float, which conflicts \
+            with the type hint iox::column_type::field::integer for it"
+        );
+    }
+
+    #[test]
+    fn max_lines() {
+        let catalog = test_catalog();
+        let lp = "cpu,host=a usage=0.5 1\n\
+            \n\
+            cpu,host=b usage=0.7 2\n\
+            cpu,host=c usage=0.9 3";
+        let validate = |max_lines: usize, policy: MaxLinesPolicy| {
+            validate_lp(
+                &catalog,
+                |validator| validator.with_max_lines(max_lines, policy),
+                lp,
+                Precision::Nanosecond,
+            )
+        };
+
+        let err = validate(3, MaxLinesPolicy::Reject).unwrap_err();
+        assert!(matches!(err, Error::TooManyLines { limit: 3 }));
+        assert!(
+            catalog
+                .db_schema("test")
+                .unwrap()
+                .table_definition("cpu")
+                .is_none()
+        );
+
+        // the blank line counts towards the maximum:
+        let result = validate(3, MaxLinesPolicy::Truncate)
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 2);
+        assert!(result.lines_truncated);
+
+        let result = validate(4, MaxLinesPolicy::Reject)
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(result.line_count, 3);
+        assert!(!result.lines_truncated);
+    }
+
+    #[test]
+    fn schema_diff() {
+        let catalog = test_catalog();
+        validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5 1",
+            Precision::Nanosecond,
+        )
+        .unwrap();
+
+        let lines_parsed = validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a,region=us usage=0.5,idle=0.1 2\n\
+            mem,host=a free=1i 3",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .into_inner();
+        let diff = lines_parsed.schema_diff();
+        assert_eq!(diff.added_tables, [Arc::<str>::from("mem")]);
+        let added_columns = diff
+            .added_columns
+            .iter()
+            .map(|(table, columns)| {
+                (
+                    table.to_string(),
+                    columns
+                        .iter()
+                        .map(|(name, _)| name.to_string())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            added_columns,
+            [
+                (
+                    "cpu".to_string(),
+                    vec!["idle".to_string(), "region".to_string()]
+                ),
+                (
+                    "mem".to_string(),
+                    vec!["free".to_string(), "host".to_string(), "time".to_string()]
+                ),
+            ]
+        );
+
+        // the schemas can also be diffed directly, e.g., from the catalog:
+        assert!(
+            super::schema_diff(
+                lines_parsed.validated_schema(),
+                &catalog.db_schema("test").unwrap()
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            super::schema_diff(
+                lines_parsed.before_schema(),
+                lines_parsed.validated_schema()
+            ),
+            diff
+        );
+    }
+
+    #[test]
+    fn column_name_pattern() {
+        let catalog = test_catalog();
         let namespace = NamespaceName::new("test").unwrap();
-        let catalog = Arc::new(Catalog::new(node_id, instance_id));
-        let result = WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog), 0)
+        validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,Host=a usage=0.5 1",
+            Precision::Nanosecond,
+        )
+        .unwrap();
+
+        let validator = WriteValidator::initialize(namespace, Arc::clone(&catalog), 0)
             .unwrap()
+            .with_column_name_pattern(regex::Regex::new("^[a-z][a-z0-9_]*$").unwrap());
+        let lp = "cpu,Host=b usage=0.7 2\n\
+            cpu,Host=c,Region=us usage=0.9 3\n\
+            cpu,Host=d usage=0.1,idle_pct=0.2 4\n\
+            mem,host=a FreeBytes=1i 5";
+        let checked = validator
+            .check_lines(lp, Time::from_timestamp_nanos(0), Precision::Nanosecond)
+            .unwrap();
+        let result = validator
             .parse_lines_and_update_schema(
-                "cpu,tag1=foo val1=\"bar\" 1234",
-                false,
+                lp,
+                true,
                 Time::from_timestamp_nanos(0),
-                Precision::Auto,
+                Precision::Nanosecond,
             )
             .unwrap()
             .convert_lines_to_buffer(Gen1Duration::new_5m());
+        // the existing column that does not match can still be written to:
+        assert_eq!(result.line_count, 2);
+        assert_eq!(checked.line_count, 2);
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| e.error_message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "column 'Region' on line 2 would be created with a name that does not match the \
+                pattern '^[a-z][a-z0-9_]*$'",
+                "column 'FreeBytes' on line 4 would be created with a name that does not match \
+                the pattern '^[a-z][a-z0-9_]*$'",
+            ]
+        );
+        assert_eq!(
+            checked
+                .errors
+                .iter()
+                .map(|e| e.line_number)
+                .collect::<Vec<_>>(),
+            [2, 4]
+        );
+    }
+
+    #[test]
+    fn into_row_iter() {
+        let catalog = test_catalog();
+        let lp = "cpu,host=a usage=0.5 1\n\
+            mem,host=a free=1i 2\n\
+            cpu,host=b usage=0.7 3";
+        let lines_parsed = || {
+            validate_lp(&catalog, |validator| validator, lp, Precision::Nanosecond)
+                .unwrap()
+                .into_inner()
+        };
 
+        let mut rows = lines_parsed().into_row_iter();
+        assert_eq!(rows.next().map(|row| row.time), Some(1));
+        assert_eq!(rows.map(|row| row.time).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(
+            lines_parsed().into_row_iter().collect::<Vec<_>>(),
+            lines_parsed().to_rows()
+        );
+    }
+
+    #[test]
+    fn time_column_name_is_reserved() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        // lines for both a new and an existing table:
+        let lp = "cpu,time=a usage=0.5 1\n\
+            cpu time=\"foo\" 2\n\
+            cpu,host=a usage=0.7 3\n\
+            cpu,time=b usage=0.9 4\n\
+            cpu time=\"bar\" 5";
+        let validator = WriteValidator::initialize(namespace, Arc::clone(&catalog), 0).unwrap();
+        let checked = validator
+            .check_lines(lp, Time::from_timestamp_nanos(0), Precision::Nanosecond)
+            .unwrap();
+        let result = validator
+            .parse_lines_and_update_schema(
+                lp,
+                true,
+                Time::from_timestamp_nanos(0),
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert_eq!(checked.line_count, 1);
         assert_eq!(result.line_count, 1);
-        assert_eq!(result.field_count, 1);
-        assert_eq!(result.index_count, 1);
-        assert!(result.errors.is_empty());
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| (e.line_number, e.error_message.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    1,
+                    "tag 'time' on line 1 has the name of the time column, which is reserved \
+                    for the timestamp of the line"
+                ),
+                (
+                    2,
+                    "field 'time' on line 2 has the name of the time column, which is reserved \
+                    for the timestamp of the line"
+                ),
+                (
+                    4,
+                    "tag 'time' on line 4 has the name of the time column, which is reserved \
+                    for the timestamp of the line"
+                ),
+                (
+                    5,
+                    "field 'time' on line 5 has the name of the time column, which is reserved \
+                    for the timestamp of the line"
+                ),
+            ]
+        );
 
-        assert_eq!(result.valid_data.database_name.as_ref(), namespace.as_str());
-        // cpu table
-        let batch = result
+        // the line's own timestamp is written to the time column:
+        let cpu = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        assert_eq!(
+            cpu.field_type_by_name(TIME_COLUMN_NAME),
+            Some(InfluxColumnType::Timestamp)
+        );
+        let time_id = cpu.column_name_to_id(TIME_COLUMN_NAME).unwrap();
+        let times = result
             .valid_data
             .table_chunks
-            .get(&TableId::from(0))
-            .unwrap();
-        assert_eq!(batch.row_count(), 1);
+            .values()
+            .flat_map(|chunks| chunks.chunk_time_to_chunk.values())
+            .flat_map(|chunk| chunk.rows.iter())
+            .flat_map(|row| row.fields.iter().filter(|f| f.id == time_id))
+            .map(|f| f.value.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(times, [FieldData::Timestamp(3)]);
+    }
 
-        // Validate another write, the result should be very similar, but now the catalog
-        // has the table/columns added, so it will excercise a different code path:
-        let result = WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog), 0)
+    #[test]
+    fn effective_precisions() {
+        let catalog = test_catalog();
+        let lp = "cpu,host=a usage=0.5 1708976567\n\
+            cpu,host=b usage=0.7 1708976567000\n\
+            cpu,host=c usage=0.9\n\
+            cpu,host=d usage=0.1 1708976567000000000";
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_effective_precisions(),
+            lp,
+            Precision::Auto,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        // the line without a timestamp is given the ingest time, so has no precision:
+        assert_eq!(
+            result.effective_precisions,
+            [
+                (1, Precision::Second),
+                (2, Precision::Millisecond),
+                (4, Precision::Nanosecond)
+            ]
+        );
+
+        // not recorded unless enabled:
+        let result = validate_lp(&catalog, |validator| validator, lp, Precision::Auto)
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert!(result.effective_precisions.is_empty());
+    }
+
+    #[test]
+    fn measurement_splitter() {
+        let catalog = test_catalog();
+        let split = |measurement: &str| -> (Arc<str>, Vec<(Arc<str>, Arc<str>)>) {
+            let mut parts = measurement.split('.');
+            let table = parts.next().unwrap_or_default().into();
+            let mut tags = Vec::new();
+            while let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+                tags.push((key.into(), value.into()));
+            }
+            (table, tags)
+        };
+        let result = validate_lp(
+            &catalog,
+            |validator| validator.with_measurement_splitter(split),
+            "cpu.region.us,host=a usage=0.5 1\n\
+            cpu.region.eu.host.b usage=0.7 2\n\
+            cpu.host.c,host=d usage=0.9 3\n\
+            cpu usage=0.1 4",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.line_count, 4);
+        assert_eq!(result.index_count, 5);
+
+        let db = catalog.db_schema("test").unwrap();
+        assert_eq!(db.table_count(), 1);
+        let table = db.table_definition("cpu").unwrap();
+        let tag_names = table
+            .series_key_names()
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(tag_names, ["host", "region"]);
+
+        // a tag in the line takes precedence over one split from the measurement name:
+        let host_id = table.column_name_to_id("host").unwrap();
+        let hosts = result.valid_data.table_chunks[&table.table_id].chunk_time_to_chunk[&0]
+            .rows
+            .iter()
+            .filter_map(|row| {
+                row.fields.iter().find_map(|f| match &f.value {
+                    FieldData::Tag(v) if f.id == host_id => Some(v.as_str()),
+                    _ => None,
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(hosts, ["a", "b", "d"]);
+    }
+
+    #[test]
+    fn schema_consistency_check() {
+        let validate = |catalog: &Arc<Catalog>, lp: &str| {
+            validate_lp(
+                catalog,
+                |validator| validator.with_schema_consistency_check(),
+                lp,
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .into_inner()
+        };
+        let catalog = test_catalog();
+        validate(&catalog, "cpu,host=a usage=0.5 1\nmem,host=a used=1i 1");
+        let parsed = validate(&catalog, "cpu,host=a,region=us usage=0.5,idle=0.1 2");
+        let catalog_schema = catalog.db_schema("test").unwrap();
+        assert!(super::schema_mismatches(&parsed.validated_schema, &catalog_schema).is_empty());
+
+        // a schema with the same tables and columns, but created independently, has different
+        // ids, like a schema that has drifted from the catalog:
+        let other_catalog = test_catalog();
+        validate(&other_catalog, "cpu,host=a usage=0.5 1");
+        let mismatches = super::schema_mismatches(
+            &parsed.validated_schema,
+            &other_catalog.db_schema("test").unwrap(),
+        );
+        assert_eq!(mismatches.len(), 2, "{mismatches:?}");
+        assert!(
+            mismatches
+                .iter()
+                .all(|m| m.ends_with("is not in the catalog"))
+        );
+    }
+
+    #[test]
+    fn check_lines_matches_write() {
+        let catalog = test_catalog();
+        let namespace = NamespaceName::new("test").unwrap();
+        let validator = WriteValidator::initialize(namespace, Arc::clone(&catalog), 0)
             .unwrap()
+            .with_tag_cardinality_budgets(HashMap::from([(Arc::from("user_id"), 2)]))
+            .with_row_validator(|row, table_def| {
+                let Some(col_id) = table_def.column_name_to_id("count") else {
+                    return Ok(());
+                };
+                match row.fields.iter().find(|f| f.id == col_id).map(|f| &f.value) {
+                    Some(FieldData::Integer(v)) if *v < 0 => Err(format!("count {v} is negative")),
+                    _ => Ok(()),
+                }
+            });
+        let lp = "logins,user_id=a count=1i 1\n\
+            logins,user_id=a count=-1i 2\n\
+            logins,user_id=b count=1i 3\n\
+            logins,user_id=c count=1i 4\n\
+            logins,user_id=a count=\"x\" 5";
+        let checked = validator
+            .check_lines(lp, Time::from_timestamp_nanos(0), Precision::Nanosecond)
+            .unwrap();
+        let result = validator
             .parse_lines_and_update_schema(
-                "cpu,tag1=foo val1=\"bar\" 1235",
-                false,
+                lp,
+                true,
                 Time::from_timestamp_nanos(0),
-                Precision::Auto,
+                Precision::Nanosecond,
             )
             .unwrap()
             .convert_lines_to_buffer(Gen1Duration::new_5m());
+        let line_numbers =
+            |errors: &[WriteLineError]| errors.iter().map(|e| e.line_number).collect::<Vec<_>>();
+        assert_eq!(line_numbers(&result.errors), [2, 4, 5]);
+        assert_eq!(line_numbers(&checked.errors), line_numbers(&result.errors));
+        assert_eq!(checked.line_count, result.line_count);
+        assert_eq!(checked.index_count, result.index_count);
+        assert_eq!(checked.field_count, result.field_count);
+    }
 
-        println!("result: {result:?}");
-        assert_eq!(result.line_count, 1);
-        assert_eq!(result.field_count, 1);
-        assert_eq!(result.index_count, 1);
-        assert!(result.errors.is_empty());
+    #[test]
+    fn rejected_lines_do_not_count_against_tag_cardinality_budgets() {
+        let catalog = test_catalog();
+        let result = validate_lp(
+            &catalog,
+            |validator| {
+                validator
+                    .with_tag_cardinality_budgets(HashMap::from([(Arc::from("user_id"), 2)]))
+                    .with_row_validator(|row, table_def| {
+                        let Some(col_id) = table_def.column_name_to_id("count") else {
+                            return Ok(());
+                        };
+                        match row.fields.iter().find(|f| f.id == col_id).map(|f| &f.value) {
+                            Some(FieldData::Integer(v)) if *v < 0 => {
+                                Err(format!("count {v} is negative"))
+                            }
+                            _ => Ok(()),
+                        }
+                    })
+            },
+            "logins,user_id=a count=1i 1\n\
+            logins,user_id=b count=\"x\" 2\n\
+            logins,user_id=c count=-1i 3\n\
+            logins,user_id=d count=1i 4\n\
+            logins,user_id=e count=1i 5",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        // only the values of accepted lines count, so line 4 is within the budget:
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| e.line_number)
+                .collect::<Vec<_>>(),
+            [2, 3, 5]
+        );
+        assert_eq!(result.line_count, 2);
+    }
 
-        // Validate another write, this time adding a new field:
-        let result = WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog), 0)
+    #[test]
+    fn skip_empty_tags_by_table_name() {
+        let catalog = test_catalog();
+        // both lines are written to the `cpu` table, where `host` is not always empty:
+        let result = validate_lp(
+            &catalog,
+            |validator| {
+                validator
+                    .with_skip_empty_tags()
+                    .with_measurement_splitter(|measurement| {
+                        let (table, host) = measurement
+                            .split_once(".host.")
+                            .unwrap_or((measurement, ""));
+                        (table.into(), vec![("host".into(), host.into())])
+                    })
+            },
+            "cpu.host. v=1 1\n\
+            cpu.host.a v=1 2",
+            Precision::Nanosecond,
+        )
+        .unwrap()
+        .convert_lines_to_buffer(Gen1Duration::new_5m());
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+        assert_eq!(result.index_count, 2);
+        let table = catalog
+            .db_schema("test")
             .unwrap()
-            .parse_lines_and_update_schema(
-                "cpu,tag1=foo val1=\"bar\",val2=false 1236",
+            .table_definition("cpu")
+            .unwrap();
+        assert!(table.column_name_to_id("host").is_some());
+    }
+
+    #[test]
+    fn validate_multi_db_isolates_entries() {
+        let catalog = test_catalog();
+        let foo = NamespaceName::new("foo").unwrap();
+        // the unterminated string of the first entry does not swallow the lines of the next:
+        let results = super::validate_multi_db(
+            &catalog,
+            vec![
+                (foo.clone(), "cpu,host=a name=\"unterminated 1"),
+                (foo.clone(), "cpu,host=b usage=0.7 2\nmem,host=b free=\"x"),
+            ],
+            true,
+            Time::from_timestamp_nanos(0),
+            Precision::Nanosecond,
+            Gen1Duration::new_5m(),
+        );
+        let [first, second] = &results[&foo][..] else {
+            panic!("expected a result per entry");
+        };
+        let first = first.as_ref().unwrap();
+        assert_eq!(first.line_count, 0);
+        assert_eq!(first.errors.len(), 1);
+        assert_eq!(first.errors[0].line_number, 1);
+
+        let second = second.as_ref().unwrap();
+        assert_eq!(second.line_count, 1);
+        assert_eq!(second.errors.len(), 1);
+        assert_eq!(second.errors[0].line_number, 2);
+        assert!(
+            catalog
+                .db_schema("foo")
+                .unwrap()
+                .table_definition("cpu")
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn column_id_verification_with_stale_schema() {
+        let catalog = test_catalog();
+        validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5 1",
+            Precision::Nanosecond,
+        )
+        .unwrap();
+
+        // a schema with a column that never reached the catalog, as if applying the catalog
+        // batch that added it had partially failed:
+        let mut stale_schema = catalog.db_schema("test").unwrap().as_ref().clone();
+        let mut cpu = stale_schema
+            .table_definition("cpu")
+            .unwrap()
+            .as_ref()
+            .clone();
+        let idle_id = ColumnId::new();
+        cpu.add_columns(vec![(
+            idle_id,
+            "idle".into(),
+            InfluxColumnType::Field(InfluxFieldType::Float),
+        )])
+        .unwrap();
+        stale_schema
+            .insert_table(cpu.table_id, Arc::new(cpu))
+            .unwrap();
+        let stale_schema = Arc::new(stale_schema);
+
+        let validate = |verify: bool, lp: &str| {
+            let validator = WriteValidator::initialize_frozen(
+                Arc::clone(&stale_schema),
+                Arc::clone(&catalog),
+                0,
+            );
+            let validator = if verify {
+                validator.with_column_id_verification()
+            } else {
+                validator
+            };
+            validator.parse_lines_and_update_schema(
+                lp,
                 false,
                 Time::from_timestamp_nanos(0),
-                Precision::Auto,
+                Precision::Nanosecond,
             )
+        };
+        // the column is in the validator's schema, so the write needs no catalog change, and
+        // its rows only diverge from the catalog unless they are verified:
+        let lp = "cpu,host=a usage=0.7,idle=0.1 2";
+        assert!(validate(false, lp).is_ok());
+        let err = validate(true, lp).unwrap_err();
+        assert!(
+            matches!(err, Error::ColumnNotInCatalog { column_id, .. } if column_id == idle_id),
+            "{err}"
+        );
+
+        // the rows are verified before the column that the write adds is applied to the
+        // catalog, so the catalog is left unchanged:
+        let sequence = catalog.sequence_number();
+        let err = validate(true, "cpu,host=a usage=0.7,idle=0.1,system=0.2 3").unwrap_err();
+        assert!(
+            matches!(err, Error::ColumnNotInCatalog { column_id, .. } if column_id == idle_id),
+            "{err}"
+        );
+        assert_eq!(catalog.sequence_number(), sequence);
+        let cpu = catalog
+            .db_schema("test")
             .unwrap()
-            .convert_lines_to_buffer(Gen1Duration::new_5m());
+            .table_definition("cpu")
+            .unwrap();
+        assert!(cpu.column_name_to_id("system").is_none());
+    }
 
-        println!("result: {result:?}");
-        assert_eq!(result.line_count, 1);
-        assert_eq!(result.field_count, 2);
-        assert_eq!(result.index_count, 1);
+    #[test]
+    #[should_panic(expected = "does not match the catalog")]
+    fn schema_consistency_check_panics_on_mismatch() {
+        let catalog = test_catalog();
+        validate_lp(
+            &catalog,
+            |validator| validator,
+            "cpu,host=a usage=0.5 1",
+            Precision::Nanosecond,
+        )
+        .unwrap();
+        // a schema with a column that the catalog does not have:
+        let mut stale_schema = catalog.db_schema("test").unwrap().as_ref().clone();
+        let mut cpu = stale_schema
+            .table_definition("cpu")
+            .unwrap()
+            .as_ref()
+            .clone();
+        cpu.add_columns(vec![(
+            ColumnId::new(),
+            "idle".into(),
+            InfluxColumnType::Field(InfluxFieldType::Float),
+        )])
+        .unwrap();
+        stale_schema
+            .insert_table(cpu.table_id, Arc::new(cpu))
+            .unwrap();
+
+        let _ = WriteValidator::initialize_frozen(Arc::new(stale_schema), Arc::clone(&catalog), 0)
+            .with_schema_consistency_check()
+            .parse_lines_and_update_schema(
+                "cpu,host=a usage=0.7,system=0.2 2",
+                false,
+                Time::from_timestamp_nanos(0),
+                Precision::Nanosecond,
+            );
+    }
+
+    #[test]
+    fn tags_outside_explicit_series_key() {
+        let catalog = test_catalog();
+        let validate = |explicit_series_keys: bool, lp: &str| {
+            let configure = |validator: WriteValidator<WithCatalog>| {
+                if explicit_series_keys {
+                    validator.with_explicit_series_keys()
+                } else {
+                    validator
+                }
+            };
+            validate_lp(&catalog, configure, lp, Precision::Nanosecond)
+                .unwrap()
+                .into_inner()
+        };
+        // the table is created with its tags as its declared series key:
+        let result = validate(true, "cpu,host=a usage=0.5 1");
+        assert!(result.errors.is_empty());
+        let cpu = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        assert!(cpu.explicit_series_key);
+
+        // lines that add a tag to the table are rejected, whether they are written like v1 or v3:
+        for explicit_series_keys in [false, true] {
+            let result = validate(
+                explicit_series_keys,
+                "cpu,host=b,region=us usage=0.7 2\ncpu,host=c usage=0.9 3",
+            );
+            assert_eq!(result.errors.len(), 1);
+            assert_eq!(
+                result.errors[0].error_message,
+                "tag 'region' on line 1 is not in the series key of table 'cpu', which was \
+                declared when the table was created: host"
+            );
+            assert_eq!(result.lines.len(), 1);
+        }
+        let cpu = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        assert_eq!(cpu.series_key_names().len(), 1);
+        assert!(!cpu.column_exists("region"));
+    }
+
+    #[test]
+    fn explicit_series_key_for_table_of_written_tags() {
+        let catalog = test_catalog();
+        let validate = |explicit_series_keys: bool, lp: &str| {
+            let configure = |validator: WriteValidator<WithCatalog>| {
+                if explicit_series_keys {
+                    validator.with_explicit_series_keys()
+                } else {
+                    validator
+                }
+            };
+            validate_lp(&catalog, configure, lp, Precision::Nanosecond)
+                .unwrap()
+                .into_inner()
+        };
+        // the table is created by a v1 write, so its series key is the tags written to it:
+        let result = validate(false, "cpu,host=a usage=0.5 1");
         assert!(result.errors.is_empty());
+        let cpu = catalog
+            .db_schema("test")
+            .unwrap()
+            .table_definition("cpu")
+            .unwrap();
+        assert!(!cpu.explicit_series_key);
 
-        Ok(())
+        let result = validate(true, "cpu,host=a usage=0.7 2\nmem,host=a free=1i 3");
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(
+            result.errors[0].error_message,
+            "line 1 declares an explicit series key for table 'cpu', whose series key is made \
+            up of the tags written to it"
+        );
+        assert_eq!(result.lines.len(), 1);
+        assert!(
+            catalog
+                .db_schema("test")
+                .unwrap()
+                .table_definition("mem")
+                .unwrap()
+                .explicit_series_key
+        );
+
+        // v1 writes can still add tags to the table:
+        let result = validate(false, "cpu,host=a,region=us usage=0.9 4");
+        assert!(result.errors.is_empty());
+    }
+
+    #[test]
+    fn parse_lines_with_scratch() {
+        let lp = "cpu,host=a usage=0.5 1\n\
+            cpu,host=b usage=0.7,idle=0.1 2\n\
+            cpu,host=c usage=\"bad\" 3\n\
+            mem,host=a,region=us free=1i 4";
+        let rows = |lines_parsed: Result<WriteValidator<LinesParsed>, Error>| {
+            lines_parsed
+                .unwrap()
+                .into_inner()
+                .to_rows()
+                .into_iter()
+                .map(|row| (row.time, row.fields.len()))
+                .collect::<Vec<_>>()
+        };
+        let new_validator = || {
+            let namespace = NamespaceName::new("test").unwrap();
+            WriteValidator::initialize(namespace, test_catalog(), 0).unwrap()
+        };
+        let ingest_time = Time::from_timestamp_nanos(0);
+
+        let mut validator = new_validator();
+        let expected = [
+            rows(validator.parse_lines_and_update_schema_reusing(
+                lp,
+                true,
+                ingest_time,
+                Precision::Nanosecond,
+            )),
+            rows(validator.parse_lines_and_update_schema_reusing(
+                lp,
+                true,
+                ingest_time,
+                Precision::Nanosecond,
+            )),
+        ];
+        assert_eq!(expected[0], [(1, 3), (2, 4), (4, 4)]);
+
+        // the same writes with a scratch buffer reused across them produce the same rows, and
+        // the buffers keep their allocations, without holding on to any values:
+        let mut validator = new_validator();
+        let mut scratch = Scratch::new();
+        for expected in expected {
+            let actual = rows(validator.parse_lines_with_scratch(
+                &mut scratch,
+                lp,
+                true,
+                ingest_time,
+                Precision::Nanosecond,
+            ));
+            assert_eq!(actual, expected);
+            assert!(scratch.tags.is_empty());
+            assert!(scratch.tags.capacity() >= 2);
+            assert!(scratch.new_tag_values.is_empty());
+        }
     }
 }